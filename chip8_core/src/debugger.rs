@@ -0,0 +1,321 @@
+// Debugger: PC breakpoints, memory watchpoints, stepping and a read-only view of the machine.
+//
+// Pausing is part of the Emulator itself, so a frontend can keep calling tick()
+// as usual: while paused, tick() does nothing until resume() or one of the step functions.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::ops::RangeInclusive;
+
+use crate::{Chip8Error, Emulator, Instruction, NUM_REGS};
+
+// Why the Emulator is paused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    // PC reached a breakpoint; the instruction at addr has not run yet.
+    Breakpoint { addr: usize },
+    // step, step_over or run_to_return finished.
+    StepComplete,
+    // step had no instruction to run: the program has halted, or is waiting for the vertical
+    // blank after a draw (display wait quirk), which only the end of the frame brings.
+    Blocked,
+    // A pausing watchpoint was hit; the instruction that accessed memory has completed.
+    Watchpoint(WatchHit),
+    // pause() was called.
+    Requested,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccess {
+    Read,
+    Write,
+}
+
+// Which accesses a watchpoint traps on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(&self, access: MemoryAccess) -> bool {
+        match self {
+            WatchKind::Read => access == MemoryAccess::Read,
+            WatchKind::Write => access == MemoryAccess::Write,
+            WatchKind::ReadWrite => true,
+        }
+    }
+}
+
+// Traps accesses to a range of RAM addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addresses: RangeInclusive<usize>,
+    pub kind: WatchKind,
+    // Pause the Emulator after the instruction that hit the watchpoint, instead of only logging the hit.
+    pub pause: bool,
+}
+
+// A recorded access to a watched address.
+// For reads, old_value and new_value are both the value read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub addr: usize,
+    pub access: MemoryAccess,
+    pub pc: usize,
+    pub opcode: u16,
+    pub old_value: u8,
+    pub new_value: u8,
+}
+
+// Hits are kept until take_watch_hits is called; beyond this the oldest are dropped.
+const MAX_WATCH_HITS: usize = 256;
+
+// Snapshot of the CPU registers and timers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registers {
+    pub v: [u8; NUM_REGS],
+    pub i: usize,
+    pub pc: usize,
+    pub sp: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+// Parses a hex address such as `0x2A4` or `2A4`.
+pub fn parse_address(text: &str) -> Option<usize> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16).ok()
+}
+
+// The registers, the opcode at PC and the stack, as the frontends print them:
+//   PC 0x2A4 [D015]  I 0x2F0  DT   0  ST   0
+//   V0 00  V1 00  ...  V7 00
+//   V8 00  V9 00  ...  VF 00
+//   Stack [0x202]
+pub fn format_registers(chip8: &Emulator) -> String {
+    let regs = chip8.registers();
+    let opcode = chip8.peek_opcode().map_or(String::from("????"), |opcode| format!("{:04X}", opcode));
+    let mut text = format!("PC {:#05X} [{}]  I {:#05X}  DT {:3}  ST {:3}\n", regs.pc, opcode, regs.i, regs.delay_timer, regs.sound_timer);
+    for (row, values) in regs.v.chunks(8).enumerate() {
+        let line: Vec<String> = values.iter().enumerate()
+            .map(|(i, value)| format!("V{:X} {:02X}", row * 8 + i, value))
+            .collect();
+        text += &line.join("  ");
+        text += "\n";
+    }
+    let stack: Vec<String> = chip8.stack().iter().map(|addr| format!("{:#05X}", addr)).collect();
+    text += &format!("Stack [{}]\n", stack.join(", "));
+    text
+}
+
+// Condition that pauses the Emulator again after resuming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RunUntil {
+    // Back at return_addr with the stack as deep as before the CALL (step over).
+    Return { return_addr: usize, stack_pointer: usize },
+    // The stack is shallower than stack_pointer (run to return).
+    StackBelow { stack_pointer: usize },
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DebugState {
+    pub(crate) breakpoints: BTreeSet<usize>,
+    pub(crate) stop_reason: Option<StopReason>,
+    pub(crate) run_until: Option<RunUntil>,
+    // Set when resuming from a breakpoint, so the instruction under it can run.
+    pub(crate) skip_breakpoint: bool,
+    pub(crate) watchpoints: Vec<Watchpoint>,
+    pub(crate) watch_hits: VecDeque<WatchHit>,
+    // (PC, opcode) of the instruction being executed, for reporting watch hits.
+    pub(crate) current_instruction: (usize, u16),
+}
+
+impl Emulator {
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.debug.breakpoints.insert(addr);
+    }
+
+    // Returns false if there was no breakpoint at addr.
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.debug.breakpoints.remove(&addr)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.debug.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.debug.breakpoints.iter().copied()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.debug.watchpoints.push(watchpoint);
+    }
+
+    // Returns false if no such watchpoint was set.
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let count = self.debug.watchpoints.len();
+        self.debug.watchpoints.retain(|w| w != watchpoint);
+        self.debug.watchpoints.len() != count
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.debug.watchpoints.clear();
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.debug.watchpoints
+    }
+
+    // Returns and forgets the watchpoint hits recorded since the last call, oldest first.
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        self.debug.watch_hits.drain(..).collect()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.debug.stop_reason.is_some()
+    }
+
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.debug.stop_reason
+    }
+
+    pub fn pause(&mut self) {
+        self.debug.run_until = None;
+        self.debug.stop_reason = Some(StopReason::Requested);
+    }
+
+    pub fn resume(&mut self) {
+        self.debug.skip_breakpoint = self.debug.stop_reason.is_some();
+        self.debug.stop_reason = None;
+    }
+
+    // Executes exactly one instruction, ignoring breakpoints. A paused Emulator stays paused.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if self.halted || self.waiting_for_vblank {
+            if self.is_paused() {
+                self.debug.stop_reason = Some(StopReason::Blocked);
+            }
+            return Ok(());
+        }
+        let was_paused = self.debug.stop_reason.take().is_some();
+        self.debug.skip_breakpoint = true;
+        let result = self.tick();
+        self.debug.skip_breakpoint = false;
+        if was_paused && self.debug.stop_reason.is_none() {
+            self.debug.stop_reason = Some(StopReason::StepComplete);
+        }
+        result
+    }
+
+    // Like step, but runs a CALL until the subroutine returns.
+    // The subroutine runs on later ticks, so keep ticking (and ticking timers) until paused again.
+    pub fn step_over(&mut self) -> Result<(), Chip8Error> {
+        let is_call = matches!(self.peek_opcode().and_then(Instruction::decode), Some(Instruction::Call(_)));
+        if !is_call {
+            return self.step();
+        }
+
+        self.debug.run_until = Some(RunUntil::Return {
+            return_addr: self.program_counter + 2,
+            stack_pointer: self.stack_pointer
+        });
+        self.resume();
+        Ok(())
+    }
+
+    // Resumes until the current subroutine returns.
+    // The subroutine runs on later ticks, so keep ticking (and ticking timers) until paused again.
+    pub fn run_to_return(&mut self) {
+        self.debug.run_until = Some(RunUntil::StackBelow { stack_pointer: self.stack_pointer });
+        self.resume();
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            v: self.vreg,
+            i: self.ireg,
+            pc: self.program_counter,
+            sp: self.stack_pointer,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    // Return addresses currently on the stack, oldest first.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer]
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.ram
+    }
+
+    // Opcode at the current PC, without executing it.
+    pub fn peek_opcode(&self) -> Option<u16> {
+        let higher_byte = *self.ram.get(self.program_counter)? as u16;
+        let lower_byte = *self.ram.get(self.program_counter + 1)? as u16;
+        Some((higher_byte << 8) | lower_byte)
+    }
+
+    // Called by tick before fetching. Returns true if execution should stop here.
+    pub(crate) fn check_breakpoint(&mut self) -> bool {
+        if self.debug.skip_breakpoint {
+            self.debug.skip_breakpoint = false;
+            return false;
+        }
+
+        if self.debug.breakpoints.contains(&self.program_counter) {
+            self.debug.run_until = None;
+            self.debug.stop_reason = Some(StopReason::Breakpoint { addr: self.program_counter });
+            return true;
+        }
+        false
+    }
+
+    // Called by read_ram/write_ram on every access made by an instruction.
+    pub(crate) fn check_watchpoints(&mut self, addr: usize, access: MemoryAccess, old_value: u8, new_value: u8) {
+        if self.debug.watchpoints.is_empty() {
+            return;
+        }
+
+        let mut hit = None;
+        let mut pause = false;
+        for watchpoint in &self.debug.watchpoints {
+            if watchpoint.addresses.contains(&addr) && watchpoint.kind.matches(access) {
+                let (pc, opcode) = self.debug.current_instruction;
+                hit = Some(WatchHit { addr, access, pc, opcode, old_value, new_value });
+                pause |= watchpoint.pause;
+            }
+        }
+
+        let Some(hit) = hit else {
+            return;
+        };
+        if self.debug.watch_hits.len() == MAX_WATCH_HITS {
+            self.debug.watch_hits.pop_front();
+        }
+        self.debug.watch_hits.push_back(hit);
+        if pause && !self.is_paused() {
+            self.debug.run_until = None;
+            self.debug.stop_reason = Some(StopReason::Watchpoint(hit));
+        }
+    }
+
+    // Called by tick after executing.
+    pub(crate) fn check_run_until(&mut self) {
+        let done = match self.debug.run_until {
+            Some(RunUntil::Return { return_addr, stack_pointer }) =>
+                self.program_counter == return_addr && self.stack_pointer == stack_pointer,
+            Some(RunUntil::StackBelow { stack_pointer }) => self.stack_pointer < stack_pointer,
+            None => false
+        };
+
+        if done {
+            self.debug.run_until = None;
+            self.debug.stop_reason.get_or_insert(StopReason::StepComplete);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Disassembler: turns ROM bytes back into instructions, in Cowgod or Octo syntax.
+//
+// Decodes every opcode known to any platform (CHIP-8, SUPER-CHIP and XO-CHIP) with a
+// linear sweep. Bytes that don't form a known opcode are listed as data.
+
+use std::collections::BTreeSet;
+
+use crate::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    // Cowgod's Chip-8 Technical Reference, e.g. `ADD V1, V2`.
+    Cowgod,
+    // Octo assembly, e.g. `v1 += v2`.
+    Octo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    pub addr: usize,
+    // Raw bytes: 2 for most instructions, 4 for XO-CHIP F000 NNNN, 1 for a trailing odd byte.
+    pub bytes: Vec<u8>,
+    // Target address of a jump or call.
+    pub target: Option<usize>,
+    // False if the bytes are not a known opcode and are listed as data.
+    pub is_instruction: bool,
+    pub cowgod: String,
+    pub octo: String,
+}
+
+// Name used for the label of a jump/call target in listings.
+pub fn label_name(addr: usize) -> String {
+    format!("label_{:03X}", addr)
+}
+
+fn hex_byte(value: u8) -> String {
+    format!("0x{:02X}", value)
+}
+
+fn hex_addr(value: usize) -> String {
+    format!("0x{:03X}", value)
+}
+
+// Renders the instruction in the given syntax, formatting jump/call targets with `target`.
+// `long_addr` is the word following the opcode, needed for F000 NNNN.
+// SYS calls are machine code for the host CPU, so they are listed as data.
+fn render(instruction: Instruction, long_addr: Option<u16>, syntax: Syntax, target: &dyn Fn(usize) -> String) -> Option<String> {
+    let cowgod = syntax == Syntax::Cowgod;
+
+    let text = match instruction {
+        Instruction::Sys(_) => return None,
+        Instruction::Scd(n) => if cowgod { format!("SCD {}", n) } else { format!("scroll-down {}", n) },
+        Instruction::Scu(n) => if cowgod { format!("SCU {}", n) } else { format!("scroll-up {}", n) },
+        Instruction::Cls => if cowgod { "CLS".into() } else { "clear".into() },
+        Instruction::Ret => if cowgod { "RET".into() } else { "return".into() },
+        Instruction::Scr => if cowgod { "SCR".into() } else { "scroll-right".into() },
+        Instruction::Scl => if cowgod { "SCL".into() } else { "scroll-left".into() },
+        Instruction::Exit => if cowgod { "EXIT".into() } else { "exit".into() },
+        Instruction::Low => if cowgod { "LOW".into() } else { "lores".into() },
+        Instruction::High => if cowgod { "HIGH".into() } else { "hires".into() },
+        Instruction::Jp(nnn) => if cowgod { format!("JP {}", target(nnn as usize)) } else { format!("jump {}", target(nnn as usize)) },
+        Instruction::Call(nnn) => if cowgod { format!("CALL {}", target(nnn as usize)) } else { format!(":call {}", target(nnn as usize)) },
+        Instruction::SeVxKk { x, kk } => if cowgod { format!("SE V{:X}, {}", x, hex_byte(kk)) } else { format!("if v{:x} != {} then", x, hex_byte(kk)) },
+        Instruction::SneVxKk { x, kk } => if cowgod { format!("SNE V{:X}, {}", x, hex_byte(kk)) } else { format!("if v{:x} == {} then", x, hex_byte(kk)) },
+        Instruction::SeVxVy { x, y } => if cowgod { format!("SE V{:X}, V{:X}", x, y) } else { format!("if v{:x} != v{:x} then", x, y) },
+        Instruction::LdIVxVy { x, y } => if cowgod { format!("LD [I], V{:X}-V{:X}", x, y) } else { format!("save v{:x} - v{:x}", x, y) },
+        Instruction::LdVxVyI { x, y } => if cowgod { format!("LD V{:X}-V{:X}, [I]", x, y) } else { format!("load v{:x} - v{:x}", x, y) },
+        Instruction::LdVxKk { x, kk } => if cowgod { format!("LD V{:X}, {}", x, hex_byte(kk)) } else { format!("v{:x} := {}", x, hex_byte(kk)) },
+        Instruction::AddVxKk { x, kk } => if cowgod { format!("ADD V{:X}, {}", x, hex_byte(kk)) } else { format!("v{:x} += {}", x, hex_byte(kk)) },
+        Instruction::LdVxVy { x, y } => if cowgod { format!("LD V{:X}, V{:X}", x, y) } else { format!("v{:x} := v{:x}", x, y) },
+        Instruction::OrVxVy { x, y } => if cowgod { format!("OR V{:X}, V{:X}", x, y) } else { format!("v{:x} |= v{:x}", x, y) },
+        Instruction::AndVxVy { x, y } => if cowgod { format!("AND V{:X}, V{:X}", x, y) } else { format!("v{:x} &= v{:x}", x, y) },
+        Instruction::XorVxVy { x, y } => if cowgod { format!("XOR V{:X}, V{:X}", x, y) } else { format!("v{:x} ^= v{:x}", x, y) },
+        Instruction::AddVxVy { x, y } => if cowgod { format!("ADD V{:X}, V{:X}", x, y) } else { format!("v{:x} += v{:x}", x, y) },
+        Instruction::SubVxVy { x, y } => if cowgod { format!("SUB V{:X}, V{:X}", x, y) } else { format!("v{:x} -= v{:x}", x, y) },
+        Instruction::ShrVxVy { x, y } => if cowgod { format!("SHR V{:X}, V{:X}", x, y) } else { format!("v{:x} >>= v{:x}", x, y) },
+        Instruction::SubnVxVy { x, y } => if cowgod { format!("SUBN V{:X}, V{:X}", x, y) } else { format!("v{:x} =- v{:x}", x, y) },
+        Instruction::ShlVxVy { x, y } => if cowgod { format!("SHL V{:X}, V{:X}", x, y) } else { format!("v{:x} <<= v{:x}", x, y) },
+        Instruction::SneVxVy { x, y } => if cowgod { format!("SNE V{:X}, V{:X}", x, y) } else { format!("if v{:x} == v{:x} then", x, y) },
+        Instruction::LdI(nnn) => if cowgod { format!("LD I, {}", hex_addr(nnn as usize)) } else { format!("i := {}", hex_addr(nnn as usize)) },
+        Instruction::JpV0(nnn) => if cowgod { format!("JP V0, {}", target(nnn as usize)) } else { format!("jump0 {}", target(nnn as usize)) },
+        Instruction::RndVxKk { x, kk } => if cowgod { format!("RND V{:X}, {}", x, hex_byte(kk)) } else { format!("v{:x} := random {}", x, hex_byte(kk)) },
+        Instruction::Drw { x, y, n } => if cowgod { format!("DRW V{:X}, V{:X}, {}", x, y, n) } else { format!("sprite v{:x} v{:x} {}", x, y, n) },
+        Instruction::SkpVx { x } => if cowgod { format!("SKP V{:X}", x) } else { format!("if v{:x} -key then", x) },
+        Instruction::SknpVx { x } => if cowgod { format!("SKNP V{:X}", x) } else { format!("if v{:x} key then", x) },
+        Instruction::LdILong => {
+            let addr = format!("0x{:04X}", long_addr?);
+            if cowgod { format!("LD I, long {}", addr) } else { format!("i := long {}", addr) }
+        },
+        Instruction::Plane(n) => if cowgod { format!("PLANE {}", n) } else { format!("plane {}", n) },
+        Instruction::Audio => if cowgod { "AUDIO".into() } else { "audio".into() },
+        Instruction::LdVxDt { x } => if cowgod { format!("LD V{:X}, DT", x) } else { format!("v{:x} := delay", x) },
+        Instruction::LdVxK { x } => if cowgod { format!("LD V{:X}, K", x) } else { format!("v{:x} := key", x) },
+        Instruction::LdDtVx { x } => if cowgod { format!("LD DT, V{:X}", x) } else { format!("delay := v{:x}", x) },
+        Instruction::LdStVx { x } => if cowgod { format!("LD ST, V{:X}", x) } else { format!("buzzer := v{:x}", x) },
+        Instruction::AddIVx { x } => if cowgod { format!("ADD I, V{:X}", x) } else { format!("i += v{:x}", x) },
+        Instruction::LdFVx { x } => if cowgod { format!("LD F, V{:X}", x) } else { format!("i := hex v{:x}", x) },
+        Instruction::LdHfVx { x } => if cowgod { format!("LD HF, V{:X}", x) } else { format!("i := bighex v{:x}", x) },
+        Instruction::LdBVx { x } => if cowgod { format!("LD B, V{:X}", x) } else { format!("bcd v{:x}", x) },
+        Instruction::PitchVx { x } => if cowgod { format!("PITCH V{:X}", x) } else { format!("pitch := v{:x}", x) },
+        Instruction::LdIVx { x } => if cowgod { format!("LD [I], V{:X}", x) } else { format!("save v{:x}", x) },
+        Instruction::LdVxI { x } => if cowgod { format!("LD V{:X}, [I]", x) } else { format!("load v{:x}", x) },
+        Instruction::LdRVx { x } => if cowgod { format!("LD R, V{:X}", x) } else { format!("saveflags v{:x}", x) },
+        Instruction::LdVxR { x } => if cowgod { format!("LD V{:X}, R", x) } else { format!("loadflags v{:x}", x) },
+    };
+
+    Some(text)
+}
+
+fn render_data(bytes: &[u8], syntax: Syntax) -> String {
+    let values: Vec<String> = bytes.iter().map(|byte| hex_byte(*byte)).collect();
+    match syntax {
+        Syntax::Cowgod => format!("DB {}", values.join(", ")),
+        Syntax::Octo => values.join(" ")
+    }
+}
+
+// Decodes `bytes` as a program loaded at `base_addr`, e.g. 0x200 for a ROM.
+pub fn disassemble(bytes: &[u8], base_addr: usize) -> Vec<DisassembledInstruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let addr = base_addr + offset;
+
+        if offset + 1 == bytes.len() {
+            let data = vec![bytes[offset]];
+            instructions.push(DisassembledInstruction {
+                addr,
+                cowgod: render_data(&data, Syntax::Cowgod),
+                octo: render_data(&data, Syntax::Octo),
+                bytes: data,
+                target: None,
+                is_instruction: false
+            });
+            break;
+        }
+
+        let opcode = u16::from_be_bytes([bytes[offset], bytes[offset + 1]]);
+        let long_addr = bytes.get(offset + 2..offset + 4).map(|word| u16::from_be_bytes([word[0], word[1]]));
+        let decoded = Instruction::decode(opcode);
+        let len = match decoded {
+            Some(instruction) if long_addr.is_some() => instruction.size(),
+            _ => 2
+        };
+        let raw = bytes[offset..offset + len].to_vec();
+
+        let rendered = decoded.and_then(|instruction| Some((
+            instruction,
+            render(instruction, long_addr, Syntax::Cowgod, &hex_addr)?,
+            render(instruction, long_addr, Syntax::Octo, &hex_addr)?
+        )));
+        let instruction = match rendered {
+            Some((instruction, cowgod, octo)) => DisassembledInstruction {
+                addr,
+                bytes: raw,
+                target: instruction.jump_target(),
+                is_instruction: true,
+                cowgod,
+                octo
+            },
+            _ => DisassembledInstruction {
+                addr,
+                cowgod: render_data(&raw, Syntax::Cowgod),
+                octo: render_data(&raw, Syntax::Octo),
+                bytes: raw,
+                target: None,
+                is_instruction: false
+            }
+        };
+
+        instructions.push(instruction);
+        offset += len;
+    }
+
+    instructions
+}
+
+// Formats a listing with addresses and raw bytes, naming jump/call targets with labels.
+// Octo listings keep addresses and bytes in comments.
+pub fn format_listing(instructions: &[DisassembledInstruction], syntax: Syntax) -> String {
+    // Only targets inside the listing get a label; others (often data decoded as a jump) stay as addresses.
+    let addrs: BTreeSet<usize> = instructions.iter().map(|instruction| instruction.addr).collect();
+    let labels: BTreeSet<usize> = instructions.iter()
+        .filter_map(|instruction| instruction.target)
+        .filter(|target| addrs.contains(target))
+        .collect();
+    let target = |addr: usize| if labels.contains(&addr) { label_name(addr) } else { hex_addr(addr) };
+    let mut listing = String::new();
+
+    for instruction in instructions {
+        if labels.contains(&instruction.addr) {
+            match syntax {
+                Syntax::Cowgod => listing.push_str(&format!("{}:\n", label_name(instruction.addr))),
+                Syntax::Octo => listing.push_str(&format!(": {}\n", label_name(instruction.addr)))
+            }
+        }
+
+        let raw: String = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let text = if instruction.is_instruction {
+            let opcode = u16::from_be_bytes([instruction.bytes[0], instruction.bytes[1]]);
+            let long_addr = instruction.bytes.get(2..4).map(|word| u16::from_be_bytes([word[0], word[1]]));
+            Instruction::decode(opcode)
+                .and_then(|decoded| render(decoded, long_addr, syntax, &target))
+                .unwrap_or_default()
+        } else {
+            render_data(&instruction.bytes, syntax)
+        };
+
+        match syntax {
+            Syntax::Cowgod => listing.push_str(&format!("{}  {:<8}  {}\n", hex_addr(instruction.addr), raw, text)),
+            Syntax::Octo => listing.push_str(&format!("\t{:<24}# {} {}\n", text, hex_addr(instruction.addr), raw))
+        }
+    }
+
+    listing
+}
@@ -0,0 +1,38 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    // Opcode at `pc` does not decode to any known instruction.
+    UnknownOpcode { pc: usize, opcode: u16 },
+    // CALL with every stack slot already in use.
+    StackOverflow { pc: usize },
+    // RET with nothing on the stack.
+    StackUnderflow { pc: usize },
+    // Read or write outside of RAM.
+    MemoryOutOfBounds { addr: usize },
+    // Key index outside of the 16-key hex keypad.
+    InvalidKey { key: usize },
+    // ROM does not fit between START_ADDR and the end of RAM.
+    RomTooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { pc, opcode } =>
+                write!(f, "unknown opcode {:#06X} at {:#05X}", opcode, pc),
+            Chip8Error::StackOverflow { pc } =>
+                write!(f, "stack overflow at {:#05X}", pc),
+            Chip8Error::StackUnderflow { pc } =>
+                write!(f, "stack underflow at {:#05X}", pc),
+            Chip8Error::MemoryOutOfBounds { addr } =>
+                write!(f, "memory access out of bounds at {:#X}", addr),
+            Chip8Error::InvalidKey { key } =>
+                write!(f, "invalid key {:#X}", key),
+            Chip8Error::RomTooLarge { size, max } =>
+                write!(f, "ROM is {} bytes, but at most {} bytes fit in memory", size, max),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
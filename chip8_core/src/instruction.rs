@@ -0,0 +1,276 @@
+// Instruction: a decoded opcode, independent of executing it.
+//
+// Covers every opcode of CHIP-8, SUPER-CHIP and XO-CHIP; platform() tells which one introduced it.
+// Variants are named after the Cowgod-style mnemonics used by the op_ functions in lib.rs.
+
+use crate::Platform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    // 0NNN - SYS addr (0000 is treated as a NOP)
+    Sys(u16),
+    // 00CN - SCD N (SUPER-CHIP)
+    Scd(u8),
+    // 00DN - SCU N (XO-CHIP)
+    Scu(u8),
+    // 00E0 - CLS
+    Cls,
+    // 00EE - RET
+    Ret,
+    // 00FB - SCR (SUPER-CHIP)
+    Scr,
+    // 00FC - SCL (SUPER-CHIP)
+    Scl,
+    // 00FD - EXIT (SUPER-CHIP)
+    Exit,
+    // 00FE - LOW (SUPER-CHIP)
+    Low,
+    // 00FF - HIGH (SUPER-CHIP)
+    High,
+    // 1NNN - JP addr
+    Jp(u16),
+    // 2NNN - CALL addr
+    Call(u16),
+    // 3XKK - SE Vx, KK
+    SeVxKk { x: u8, kk: u8 },
+    // 4XKK - SNE Vx, KK
+    SneVxKk { x: u8, kk: u8 },
+    // 5XY0 - SE Vx, Vy
+    SeVxVy { x: u8, y: u8 },
+    // 5XY2 - LD [I], Vx-Vy (XO-CHIP)
+    LdIVxVy { x: u8, y: u8 },
+    // 5XY3 - LD Vx-Vy, [I] (XO-CHIP)
+    LdVxVyI { x: u8, y: u8 },
+    // 6XKK - LD Vx, KK
+    LdVxKk { x: u8, kk: u8 },
+    // 7XKK - ADD Vx, KK
+    AddVxKk { x: u8, kk: u8 },
+    // 8XY0 - LD Vx, Vy
+    LdVxVy { x: u8, y: u8 },
+    // 8XY1 - OR Vx, Vy
+    OrVxVy { x: u8, y: u8 },
+    // 8XY2 - AND Vx, Vy
+    AndVxVy { x: u8, y: u8 },
+    // 8XY3 - XOR Vx, Vy
+    XorVxVy { x: u8, y: u8 },
+    // 8XY4 - ADD Vx, Vy
+    AddVxVy { x: u8, y: u8 },
+    // 8XY5 - SUB Vx, Vy
+    SubVxVy { x: u8, y: u8 },
+    // 8XY6 - SHR Vx {, Vy}
+    ShrVxVy { x: u8, y: u8 },
+    // 8XY7 - SUBN Vx, Vy
+    SubnVxVy { x: u8, y: u8 },
+    // 8XYE - SHL Vx {, Vy}
+    ShlVxVy { x: u8, y: u8 },
+    // 9XY0 - SNE Vx, Vy
+    SneVxVy { x: u8, y: u8 },
+    // ANNN - LD I, addr
+    LdI(u16),
+    // BNNN - JP V0, addr (BXNN - JP Vx, XNN with the jump quirk)
+    JpV0(u16),
+    // CXKK - RND Vx, KK
+    RndVxKk { x: u8, kk: u8 },
+    // DXYN - DRW Vx, Vy, N
+    Drw { x: u8, y: u8, n: u8 },
+    // EX9E - SKP Vx
+    SkpVx { x: u8 },
+    // EXA1 - SKNP Vx
+    SknpVx { x: u8 },
+    // F000 NNNN - LD I, long NNNN (XO-CHIP); the address is the word following the opcode.
+    LdILong,
+    // FN01 - PLANE N (XO-CHIP)
+    Plane(u8),
+    // F002 - AUDIO (XO-CHIP)
+    Audio,
+    // FX07 - LD Vx, DT
+    LdVxDt { x: u8 },
+    // FX0A - LD Vx, K
+    LdVxK { x: u8 },
+    // FX15 - LD DT, Vx
+    LdDtVx { x: u8 },
+    // FX18 - LD ST, Vx
+    LdStVx { x: u8 },
+    // FX1E - ADD I, Vx
+    AddIVx { x: u8 },
+    // FX29 - LD F, Vx
+    LdFVx { x: u8 },
+    // FX30 - LD HF, Vx (SUPER-CHIP)
+    LdHfVx { x: u8 },
+    // FX33 - LD B, Vx
+    LdBVx { x: u8 },
+    // FX3A - PITCH Vx (XO-CHIP)
+    PitchVx { x: u8 },
+    // FX55 - LD [I], Vx
+    LdIVx { x: u8 },
+    // FX65 - LD Vx, [I]
+    LdVxI { x: u8 },
+    // FX75 - LD R, Vx (SUPER-CHIP)
+    LdRVx { x: u8 },
+    // FX85 - LD Vx, R (SUPER-CHIP)
+    LdVxR { x: u8 },
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Option<Instruction> {
+        let nibbles = (
+            ((opcode & 0xF000) >> 12) as u8,
+            ((opcode & 0x0F00) >> 8) as u8,
+            ((opcode & 0x00F0) >> 4) as u8,
+            (opcode & 0x000F) as u8
+        );
+
+        let nnn = opcode & 0x0FFF;
+        let kk = (opcode & 0x00FF) as u8;
+        let x = nibbles.1;
+        let y = nibbles.2;
+        let n = nibbles.3;
+
+        let instruction = match nibbles {
+            (0, 0, 0xC, _) => Instruction::Scd(n),
+            (0, 0, 0xD, _) => Instruction::Scu(n),
+            (0, 0, 0xE, 0) => Instruction::Cls,
+            (0, 0, 0xE, 0xE) => Instruction::Ret,
+            (0, 0, 0xF, 0xB) => Instruction::Scr,
+            (0, 0, 0xF, 0xC) => Instruction::Scl,
+            (0, 0, 0xF, 0xD) => Instruction::Exit,
+            (0, 0, 0xF, 0xE) => Instruction::Low,
+            (0, 0, 0xF, 0xF) => Instruction::High,
+            (0, _, _, _) => Instruction::Sys(nnn),
+            (1, _, _, _) => Instruction::Jp(nnn),
+            (2, _, _, _) => Instruction::Call(nnn),
+            (3, _, _, _) => Instruction::SeVxKk { x, kk },
+            (4, _, _, _) => Instruction::SneVxKk { x, kk },
+            (5, _, _, 0) => Instruction::SeVxVy { x, y },
+            (5, _, _, 2) => Instruction::LdIVxVy { x, y },
+            (5, _, _, 3) => Instruction::LdVxVyI { x, y },
+            (6, _, _, _) => Instruction::LdVxKk { x, kk },
+            (7, _, _, _) => Instruction::AddVxKk { x, kk },
+            (8, _, _, 0) => Instruction::LdVxVy { x, y },
+            (8, _, _, 1) => Instruction::OrVxVy { x, y },
+            (8, _, _, 2) => Instruction::AndVxVy { x, y },
+            (8, _, _, 3) => Instruction::XorVxVy { x, y },
+            (8, _, _, 4) => Instruction::AddVxVy { x, y },
+            (8, _, _, 5) => Instruction::SubVxVy { x, y },
+            (8, _, _, 6) => Instruction::ShrVxVy { x, y },
+            (8, _, _, 7) => Instruction::SubnVxVy { x, y },
+            (8, _, _, 0xE) => Instruction::ShlVxVy { x, y },
+            (9, _, _, 0) => Instruction::SneVxVy { x, y },
+            (0xA, _, _, _) => Instruction::LdI(nnn),
+            (0xB, _, _, _) => Instruction::JpV0(nnn),
+            (0xC, _, _, _) => Instruction::RndVxKk { x, kk },
+            (0xD, _, _, _) => Instruction::Drw { x, y, n },
+            (0xE, _, 9, 0xE) => Instruction::SkpVx { x },
+            (0xE, _, 0xA, 1) => Instruction::SknpVx { x },
+            (0xF, 0, 0, 0) => Instruction::LdILong,
+            (0xF, _, 0, 1) => Instruction::Plane(x),
+            (0xF, 0, 0, 2) => Instruction::Audio,
+            (0xF, _, 0, 7) => Instruction::LdVxDt { x },
+            (0xF, _, 0, 0xA) => Instruction::LdVxK { x },
+            (0xF, _, 1, 5) => Instruction::LdDtVx { x },
+            (0xF, _, 1, 8) => Instruction::LdStVx { x },
+            (0xF, _, 1, 0xE) => Instruction::AddIVx { x },
+            (0xF, _, 2, 9) => Instruction::LdFVx { x },
+            (0xF, _, 3, 0) => Instruction::LdHfVx { x },
+            (0xF, _, 3, 3) => Instruction::LdBVx { x },
+            (0xF, _, 3, 0xA) => Instruction::PitchVx { x },
+            (0xF, _, 5, 5) => Instruction::LdIVx { x },
+            (0xF, _, 6, 5) => Instruction::LdVxI { x },
+            (0xF, _, 7, 5) => Instruction::LdRVx { x },
+            (0xF, _, 8, 5) => Instruction::LdVxR { x },
+            (_, _, _, _) => return None
+        };
+
+        Some(instruction)
+    }
+
+    // Inverse of decode. Operands are masked to their field width.
+    pub fn encode(&self) -> u16 {
+        fn xkk(prefix: u16, x: u8, kk: u8) -> u16 {
+            prefix | ((x as u16 & 0xF) << 8) | kk as u16
+        }
+        fn xyn(prefix: u16, x: u8, y: u8, n: u8) -> u16 {
+            prefix | ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4) | (n as u16 & 0xF)
+        }
+
+        match *self {
+            Instruction::Sys(nnn) => nnn & 0x0FFF,
+            Instruction::Scd(n) => 0x00C0 | (n as u16 & 0xF),
+            Instruction::Scu(n) => 0x00D0 | (n as u16 & 0xF),
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::Scr => 0x00FB,
+            Instruction::Scl => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Low => 0x00FE,
+            Instruction::High => 0x00FF,
+            Instruction::Jp(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Instruction::Call(nnn) => 0x2000 | (nnn & 0x0FFF),
+            Instruction::SeVxKk { x, kk } => xkk(0x3000, x, kk),
+            Instruction::SneVxKk { x, kk } => xkk(0x4000, x, kk),
+            Instruction::SeVxVy { x, y } => xyn(0x5000, x, y, 0),
+            Instruction::LdIVxVy { x, y } => xyn(0x5000, x, y, 2),
+            Instruction::LdVxVyI { x, y } => xyn(0x5000, x, y, 3),
+            Instruction::LdVxKk { x, kk } => xkk(0x6000, x, kk),
+            Instruction::AddVxKk { x, kk } => xkk(0x7000, x, kk),
+            Instruction::LdVxVy { x, y } => xyn(0x8000, x, y, 0),
+            Instruction::OrVxVy { x, y } => xyn(0x8000, x, y, 1),
+            Instruction::AndVxVy { x, y } => xyn(0x8000, x, y, 2),
+            Instruction::XorVxVy { x, y } => xyn(0x8000, x, y, 3),
+            Instruction::AddVxVy { x, y } => xyn(0x8000, x, y, 4),
+            Instruction::SubVxVy { x, y } => xyn(0x8000, x, y, 5),
+            Instruction::ShrVxVy { x, y } => xyn(0x8000, x, y, 6),
+            Instruction::SubnVxVy { x, y } => xyn(0x8000, x, y, 7),
+            Instruction::ShlVxVy { x, y } => xyn(0x8000, x, y, 0xE),
+            Instruction::SneVxVy { x, y } => xyn(0x9000, x, y, 0),
+            Instruction::LdI(nnn) => 0xA000 | (nnn & 0x0FFF),
+            Instruction::JpV0(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Instruction::RndVxKk { x, kk } => xkk(0xC000, x, kk),
+            Instruction::Drw { x, y, n } => xyn(0xD000, x, y, n),
+            Instruction::SkpVx { x } => xkk(0xE000, x, 0x9E),
+            Instruction::SknpVx { x } => xkk(0xE000, x, 0xA1),
+            Instruction::LdILong => 0xF000,
+            Instruction::Plane(n) => xkk(0xF000, n, 0x01),
+            Instruction::Audio => 0xF002,
+            Instruction::LdVxDt { x } => xkk(0xF000, x, 0x07),
+            Instruction::LdVxK { x } => xkk(0xF000, x, 0x0A),
+            Instruction::LdDtVx { x } => xkk(0xF000, x, 0x15),
+            Instruction::LdStVx { x } => xkk(0xF000, x, 0x18),
+            Instruction::AddIVx { x } => xkk(0xF000, x, 0x1E),
+            Instruction::LdFVx { x } => xkk(0xF000, x, 0x29),
+            Instruction::LdHfVx { x } => xkk(0xF000, x, 0x30),
+            Instruction::LdBVx { x } => xkk(0xF000, x, 0x33),
+            Instruction::PitchVx { x } => xkk(0xF000, x, 0x3A),
+            Instruction::LdIVx { x } => xkk(0xF000, x, 0x55),
+            Instruction::LdVxI { x } => xkk(0xF000, x, 0x65),
+            Instruction::LdRVx { x } => xkk(0xF000, x, 0x75),
+            Instruction::LdVxR { x } => xkk(0xF000, x, 0x85),
+        }
+    }
+
+    // The first platform with this instruction.
+    pub fn platform(&self) -> Platform {
+        match self {
+            Instruction::Scd(_) | Instruction::Scr | Instruction::Scl | Instruction::Exit
+                | Instruction::Low | Instruction::High | Instruction::LdHfVx { .. }
+                | Instruction::LdRVx { .. } | Instruction::LdVxR { .. } => Platform::SuperChip,
+            Instruction::Scu(_) | Instruction::LdIVxVy { .. } | Instruction::LdVxVyI { .. }
+                | Instruction::LdILong | Instruction::Plane(_) | Instruction::Audio
+                | Instruction::PitchVx { .. } => Platform::XoChip,
+            _ => Platform::Chip8
+        }
+    }
+
+    // Size in bytes, including the address word following F000.
+    pub fn size(&self) -> usize {
+        if *self == Instruction::LdILong { 4 } else { 2 }
+    }
+
+    // Address a jump or call goes to, if this is one.
+    pub fn jump_target(&self) -> Option<usize> {
+        match *self {
+            Instruction::Jp(nnn) | Instruction::Call(nnn) | Instruction::JpV0(nnn) => Some(nnn as usize),
+            _ => None
+        }
+    }
+}
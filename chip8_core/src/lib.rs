@@ -1,18 +1,57 @@
 #![allow(non_snake_case)]
 
-use rand::Rng;
+mod debugger;
+mod disassembler;
+mod error;
+mod instruction;
+mod loader;
+mod movie;
+mod platform;
+mod quirks;
+mod random;
+mod rewind;
+mod runner;
+mod scheduler;
+mod state;
+mod timing;
+
+use debugger::DebugState;
+use movie::Recording;
+
+pub use debugger::{format_registers, parse_address, MemoryAccess, Registers, StopReason, WatchHit, WatchKind, Watchpoint};
+pub use disassembler::{disassemble, format_listing, label_name, DisassembledInstruction, Syntax};
+pub use error::Chip8Error;
+pub use instruction::Instruction;
+pub use loader::{load_rom, platform_for_rom, RomSettings};
+pub use movie::{Movie, MovieError, MovieEvent, MoviePlayer};
+pub use platform::Platform;
+pub use quirks::Quirks;
+pub use random::{RandomSource, SeededRandom};
+pub use rewind::RewindBuffer;
+pub use runner::{press_keys, run_frames, KeyPress, RunOutcome};
+pub use scheduler::{Scheduler, Timing, MAX_SPEED, MIN_SPEED, TIMER_HZ};
+pub use state::StateError;
+pub use timing::VIP_CYCLES_PER_FRAME;
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+pub const HIRES_SCREEN_WIDTH: usize = 128;   // SUPER-CHIP high resolution mode
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
 
 const RAM_SIZE: usize = 4096;
+const XO_RAM_SIZE: usize = 0x10000;  // XO-CHIP 64 KiB address space
 const NUM_REGS: usize = 16;
 const STACK_SIZE: usize = 16;
 const NUM_KEYS: usize = 16;
 const FONTSET_SIZE: usize = 80;
+const BIG_FONTSET_SIZE: usize = 100;
+const BIG_FONTSET_ADDR: usize = FONTSET_SIZE;  // Big font is stored right after the small font.
+const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;  // 4000 Hz playback rate
 const START_ADDR: usize = 0x200;  // First 512 addresses are left empty, can be used to store sprite data for font characters.
 
-const FONTSET: [u8; FONTSET_SIZE] = [
+// 4x5 hex digit sprites, also used by frontends to draw digits.
+pub const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0,   // 0
     0x20, 0x60, 0x20, 0x20, 0x70,   // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0,   // 2
@@ -31,6 +70,55 @@ const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80    // F
 ];
 
+// SUPER-CHIP 8x10 font, digits only.
+const BIG_FONTSET: [u8; BIG_FONTSET_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C,   // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C,   // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF,   // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C,   // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06,   // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C,   // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C,   // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60,   // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C,   // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C    // 9
+];
+
+// Current contents of the screen, row-major, one byte per pixel.
+// Each pixel holds one bit per bitplane, so it is 0 (off) or 1 (on) outside of XO-CHIP,
+// and a 0-3 colour index on XO-CHIP.
+pub struct Display<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: &'a [u8]
+}
+
+// Text rendering of the screen, one line per row: `.` for off pixels, `#` for on,
+// and the colour index for the other XO-CHIP colours.
+impl std::fmt::Display for Display<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.pixels.chunks(self.width) {
+            let line: String = row.iter().map(|pixel| match pixel {
+                0 => '.',
+                1 => '#',
+                _ => char::from(b'0' + (pixel & 0b11))
+            }).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+// State of an FX0A waiting for a key to be pressed and released.
+#[derive(Debug, Clone, Copy, Default)]
+struct KeyWait {
+    // Keys held the last time FX0A looked.
+    previous: [bool; NUM_KEYS],
+    // Keys pressed since the wait began.
+    pressed: [bool; NUM_KEYS],
+}
+
+#[derive(Clone)]
 pub struct Emulator {
     delay_timer: u8,
     sound_timer: u8,
@@ -40,8 +128,23 @@ pub struct Emulator {
     vreg: [u8; NUM_REGS],
     stack: [u16; STACK_SIZE],
     keys: [bool; NUM_KEYS],
-    ram: [u8; RAM_SIZE],
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT]
+    ram: Vec<u8>,
+    screen: [u8; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+    selected_planes: u8,
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pitch: u8,
+    hires: bool,
+    halted: bool,
+    rpl_flags: [u8; NUM_REGS],
+    platform: Platform,
+    quirks: Quirks,
+    waiting_for_vblank: bool,
+    // COSMAC VIP timing: machine cycles left in the current frame, or owed to it if negative.
+    vip_cycles: i32,
+    key_wait: Option<KeyWait>,
+    rng: Box<dyn RandomSource>,
+    recording: Option<Recording>,
+    debug: DebugState
 }
 
 impl Default for Emulator {
@@ -52,6 +155,21 @@ impl Default for Emulator {
 
 impl Emulator {
     pub fn new() -> Self {
+        Emulator::with_quirks(Quirks::default())
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Emulator::with_platform(Platform::Chip8, quirks)
+    }
+
+    // Same as new, but CXKK draws its random numbers from a fixed seed, so runs are reproducible.
+    pub fn with_seed(seed: u64) -> Self {
+        let mut emulator = Emulator::new();
+        emulator.set_seed(seed);
+        emulator
+    }
+
+    pub fn with_platform(platform: Platform, quirks: Quirks) -> Self {
         let mut emulator = Self {
             delay_timer: 0,
             sound_timer: 0,
@@ -61,10 +179,24 @@ impl Emulator {
             vreg: [0; NUM_REGS],
             stack: [0; STACK_SIZE],
             keys: [false; NUM_KEYS],
-            ram: [0; RAM_SIZE],
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT]
+            ram: vec![0; platform.ram_size()],
+            screen: [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+            selected_planes: 1,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
+            hires: false,
+            halted: false,
+            rpl_flags: [0; NUM_REGS],
+            platform,
+            quirks,
+            waiting_for_vblank: false,
+            vip_cycles: 0,
+            key_wait: None,
+            rng: Box::new(SeededRandom::from_entropy()),
+            recording: None,
+            debug: DebugState::default()
         };
-        emulator.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        emulator.load_fonts();
         emulator
     }
 
@@ -76,121 +208,260 @@ impl Emulator {
         self.ireg = 0;
         self.vreg = [0; NUM_REGS];
         self.stack = [0; STACK_SIZE];
-        self.ram = [0; RAM_SIZE];
+        self.ram = vec![0; self.platform.ram_size()];
         self.keys = [false; NUM_KEYS];
+        self.load_fonts();
+        self.screen = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+        self.selected_planes = 1;
+        self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        self.pitch = DEFAULT_PITCH;
+        self.hires = false;
+        self.halted = false;
+        self.waiting_for_vblank = false;
+        self.vip_cycles = 0;
+        self.key_wait = None;
+        self.debug.stop_reason = None;
+        self.debug.run_until = None;
+        // Breakpoints are kept, so a ROM can be restarted under the debugger.
+        // RPL flags are deliberately kept, like the HP-48 calculator registers they emulate.
+    }
+
+    fn load_fonts(&mut self) {
         self.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
-        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.ram[BIG_FONTSET_ADDR..BIG_FONTSET_ADDR + BIG_FONTSET_SIZE].copy_from_slice(&BIG_FONTSET);
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    // Restarts the random numbers used by CXKK from the given seed.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Box::new(SeededRandom::new(seed));
+    }
+
+    // Replaces the source of random numbers used by CXKK.
+    pub fn set_random_source(&mut self, source: Box<dyn RandomSource>) {
+        self.rng = source;
+    }
+
+    pub fn get_display(&self) -> Display<'_> {
+        let (width, height) = self.screen_size();
+        Display { width, height, pixels: &self.screen[..width * height] }
+    }
+
+    fn screen_size(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
     }
 
-    pub fn get_display(&self) -> &[bool] {
-        &self.screen
+    // True while the sound timer is running, i.e. while the buzzer should sound.
+    pub fn is_sound_active(&self) -> bool {
+        self.sound_timer > 0
     }
 
-    pub fn load_data(&mut self, data: &[u8]) {
+    // XO-CHIP 1-bit audio pattern, played back MSB first while the sound timer is active.
+    pub fn audio_pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        &self.audio_pattern
+    }
+
+    // XO-CHIP audio pattern playback rate in bits per second, set by FX3A.
+    pub fn audio_playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    // True once a SUPER-CHIP program has executed 00FD (EXIT).
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn load_data(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let max = self.ram.len() - START_ADDR;
+        if data.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: data.len(), max });
+        }
+
         let start = START_ADDR;
-        let end = (START_ADDR) + data.len();
+        let end = START_ADDR + data.len();
         self.ram[start..end].copy_from_slice(data);
+        Ok(())
     }
 
-    fn push(&mut self, value: u16) {
+    fn push(&mut self, value: u16) -> Result<(), Chip8Error> {
+        if self.stack_pointer >= STACK_SIZE {
+            return Err(Chip8Error::StackOverflow { pc: self.program_counter - 2 });
+        }
+
         self.stack[self.stack_pointer] = value;
         self.stack_pointer += 1;
+        Ok(())
     }
 
-    fn pop(&mut self) -> u16 {
+    fn pop(&mut self) -> Result<u16, Chip8Error> {
+        if self.stack_pointer == 0 {
+            return Err(Chip8Error::StackUnderflow { pc: self.program_counter - 2 });
+        }
+
         self.stack_pointer -= 1;
-        self.stack[self.stack_pointer]
+        Ok(self.stack[self.stack_pointer])
+    }
+
+    // All RAM accesses made by instructions go through read_ram/write_ram, so watchpoints see them.
+    fn read_ram(&mut self, addr: usize) -> Result<u8, Chip8Error> {
+        let value = self.ram.get(addr).copied().ok_or(Chip8Error::MemoryOutOfBounds { addr })?;
+        self.check_watchpoints(addr, MemoryAccess::Read, value, value);
+        Ok(value)
+    }
+
+    fn write_ram(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let byte = self.ram.get_mut(addr).ok_or(Chip8Error::MemoryOutOfBounds { addr })?;
+        let old_value = *byte;
+        *byte = value;
+        self.check_watchpoints(addr, MemoryAccess::Write, old_value, value);
+        Ok(())
     }
 
-    pub fn keypress(&mut self, ki: usize, is_pressed: bool) {
-        self.keys[ki] = is_pressed;
+    fn key_state(&self, key: usize) -> Result<bool, Chip8Error> {
+        self.keys.get(key).copied().ok_or(Chip8Error::InvalidKey { key })
+    }
+
+    pub fn is_key_pressed(&self, key: usize) -> bool {
+        self.key_state(key).unwrap_or(false)
+    }
+
+    pub fn keypress(&mut self, ki: usize, is_pressed: bool) -> Result<(), Chip8Error> {
+        let key = self.keys.get_mut(ki).ok_or(Chip8Error::InvalidKey { key: ki })?;
+        *key = is_pressed;
+        if let Some(recording) = &mut self.recording {
+            recording.keypress(ki, is_pressed);
+        }
+        Ok(())
     }
 
     // FETCH 16-bit opcode stored at current Program Counter.
     // Values are stored in RAM as 8-bit values, so we fetch two,
     // and combine them as Big Endian, then increment PC by 2 bytes.
-    fn fetch_opcode(&mut self) -> u16 {
-        let higher_byte = self.ram[self.program_counter] as u16;
-        let lower_byte = self.ram[(self.program_counter + 1)] as u16;
+    fn fetch_opcode(&mut self) -> Result<u16, Chip8Error> {
+        let higher_byte = self.read_ram(self.program_counter)? as u16;
+        let lower_byte = self.read_ram(self.program_counter + 1)? as u16;
         let opcode = (higher_byte << 8) | lower_byte;
 
         self.program_counter += 2;
 
-        opcode
+        Ok(opcode)
     }
 
     // TICK
     pub fn tick_timers(&mut self) {
+        self.waiting_for_vblank = false;
+        if self.recording.is_some() {
+            let screen_hash = self.screen_hash();
+            if let Some(recording) = &mut self.recording {
+                recording.end_frame(screen_hash);
+            }
+        }
+
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
 
         if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                // BEEP // TODO audio
-            }
             self.sound_timer -= 1;
         }
     }
 
-    pub fn tick(&mut self) {
-        let opcode = self.fetch_opcode();
-        self.execute(opcode);
+    // Runs a single fetch/execute cycle.
+    // On error the PC is left past the faulting opcode and the VM should not be ticked again.
+    pub fn tick(&mut self) -> Result<(), Chip8Error> {
+        // Display wait quirk: stall after a draw until the next vertical blank.
+        if self.halted || self.waiting_for_vblank || self.is_paused() {
+            return Ok(());
+        }
+        if self.check_breakpoint() {
+            return Ok(());
+        }
+        self.debug.current_instruction = (self.program_counter, self.peek_opcode().unwrap_or(0));
+
+        let opcode = self.fetch_opcode()?;
+        self.execute(opcode)?;
+        self.check_run_until();
+        Ok(())
     }
 
     // EXECUTE the opcode.
-    fn execute(&mut self, opcode: u16) {
-        let nibbles: (u16, u16, u16, u16) = (
-            (opcode & 0xF000) >> 12,
-            (opcode & 0x0F00) >> 8,
-            (opcode & 0x00F0) >> 4,
-            (opcode & 0x000F)
-        );
-
-        let nnn = (opcode & 0x0FFF) as usize;
-        let kk = (opcode & 0x00FF) as u8;
-        let x = nibbles.1 as usize;
-        let y = nibbles.2 as usize;
-        let n = nibbles.3 as usize;
-
-        match nibbles {
-            (0, 0, 0, 0) => (),
-            (0, 0, 0xE, 0) => self.op_00E0_cls(),
-            (0, 0, 0xE, 0xE) => self.op_00EE_ret(),
-            (1, _, _, _) => self.op_1NNN_jmp(nnn),
-            (2, _, _, _) => self.op_2NNN_call(nnn),
-            (3, _, _, _) => self.op_3XKK_se_vx_kk(x, kk),
-            (4, _, _, _) => self.op_4XKK_sne_vx_kk(x, kk),
-            (5, _, _, 0) => self.op_5XY0_se_vx_vy(x, y),
-            (6, _, _, _) => self.op_6XKK_ld_vx_kk(x, kk),
-            (7, _, _, _) => self.op_7XKK_add_vx_kk(x, kk),
-            (8, _, _, 0) => self.op_8XY0_ld_vx_vy(x, y),
-            (8, _, _, 1) => self.op_8XY1_or_vx_vy(x, y),
-            (8, _, _, 2) => self.op_8XY2_and_vx_vy(x, y),
-            (8, _, _, 3) => self.op_8XY3_xor_vx_vy(x, y),
-            (8, _, _, 4) => self.op_8XY4_add_vx_vy(x, y),
-            (8, _, _, 5) => self.op_8XY5_sub_vx_vy(x, y),
-            (8, _, _, 6) => self.op_8XY6_shr_vx(x),
-            (8, _, _, 7) => self.op_8XY7_subn_vx_vy(y, x),
-            (8, _, _, 0xE) => self.op_8XYE_shl_vx(x),
-            (9, _, _, 0) => self.op_9XY0_sne_vx_vy(x, y),
-            (0xA, _, _, _) => self.op_ANNN_ld_i_nnn(nnn),
-            (0xB, _, _, _) => self.op_BNNN_jmp_v0_nnn(nnn),
-            (0xC, _, _, _) => self.op_CXKK_ld_vx_rand_and_kk(x, kk),
-            (0xD, _, _, _) => self.op_DXYN_drw(x, y, n),
-            (0xE, _, 9, 0xE) => self.op_EX9E_skp_vx(x),
-            (0xE, _, 0xA, 1) => self.op_EXA1_sknp_vx(x),
-            (0xF, _, 0, 7) => self.op_FX07_ld_vx_dt(x),
-            (0xF, _, 0, 0xA) => self.op_FX0A_ld_vx_key(x),
-            (0xF, _, 1, 5) => self.op_FX15_ld_dt_vx(x),
-            (0xF, _, 1, 8) => self.op_FX18_ld_st_vx(x),
-            (0xF, _, 1, 0xE) => self.op_FX1E_add_i_vx(x),
-            (0xF, _, 2, 9) => self.op_FX29_ld_d_vx(x),
-            (0xF, _, 3, 3) => self.op_FX33_ld_b_vx(x),
-            (0xF, _, 5, 5) => self.op_FX55_ld_i_vx(x),
-            (0xF, _, 6, 5) => self.op_FX65_ld_vx_i(x),
-            (_, _, _, _) => unimplemented!("Unimplemented opcode: {:?})", nibbles)
+    fn execute(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let unknown_opcode = Chip8Error::UnknownOpcode { pc: self.program_counter - 2, opcode };
+        let instruction = Instruction::decode(opcode)
+            .filter(|instruction| instruction.platform() <= self.platform)
+            .ok_or(unknown_opcode)?;
+
+        match instruction {
+            Instruction::Sys(0) => (),
+            Instruction::Sys(_) => return Err(unknown_opcode),
+            Instruction::Scd(n) => self.op_00CN_scd(n as usize),
+            Instruction::Scu(n) => self.op_00DN_scu(n as usize),
+            Instruction::Cls => self.op_00E0_cls(),
+            Instruction::Ret => self.op_00EE_ret()?,
+            Instruction::Scr => self.op_00FB_scr(),
+            Instruction::Scl => self.op_00FC_scl(),
+            Instruction::Exit => self.op_00FD_exit(),
+            Instruction::Low => self.op_00FE_low(),
+            Instruction::High => self.op_00FF_high(),
+            Instruction::Jp(nnn) => self.op_1NNN_jmp(nnn as usize),
+            Instruction::Call(nnn) => self.op_2NNN_call(nnn as usize)?,
+            Instruction::SeVxKk { x, kk } => self.op_3XKK_se_vx_kk(x as usize, kk),
+            Instruction::SneVxKk { x, kk } => self.op_4XKK_sne_vx_kk(x as usize, kk),
+            Instruction::SeVxVy { x, y } => self.op_5XY0_se_vx_vy(x as usize, y as usize),
+            Instruction::LdIVxVy { x, y } => self.op_5XY2_ld_i_vx_vy(x as usize, y as usize)?,
+            Instruction::LdVxVyI { x, y } => self.op_5XY3_ld_vx_vy_i(x as usize, y as usize)?,
+            Instruction::LdVxKk { x, kk } => self.op_6XKK_ld_vx_kk(x as usize, kk),
+            Instruction::AddVxKk { x, kk } => self.op_7XKK_add_vx_kk(x as usize, kk),
+            Instruction::LdVxVy { x, y } => self.op_8XY0_ld_vx_vy(x as usize, y as usize),
+            Instruction::OrVxVy { x, y } => self.op_8XY1_or_vx_vy(x as usize, y as usize),
+            Instruction::AndVxVy { x, y } => self.op_8XY2_and_vx_vy(x as usize, y as usize),
+            Instruction::XorVxVy { x, y } => self.op_8XY3_xor_vx_vy(x as usize, y as usize),
+            Instruction::AddVxVy { x, y } => self.op_8XY4_add_vx_vy(x as usize, y as usize),
+            Instruction::SubVxVy { x, y } => self.op_8XY5_sub_vx_vy(x as usize, y as usize),
+            Instruction::ShrVxVy { x, y } => self.op_8XY6_shr_vx(x as usize, y as usize),
+            Instruction::SubnVxVy { x, y } => self.op_8XY7_subn_vx_vy(x as usize, y as usize),
+            Instruction::ShlVxVy { x, y } => self.op_8XYE_shl_vx(x as usize, y as usize),
+            Instruction::SneVxVy { x, y } => self.op_9XY0_sne_vx_vy(x as usize, y as usize),
+            Instruction::LdI(nnn) => self.op_ANNN_ld_i_nnn(nnn as usize),
+            Instruction::JpV0(nnn) => self.op_BNNN_jmp_v0_nnn((nnn >> 8) as usize, nnn as usize),
+            Instruction::RndVxKk { x, kk } => self.op_CXKK_ld_vx_rand_and_kk(x as usize, kk),
+            Instruction::Drw { x, y, n } => self.op_DXYN_drw(x as usize, y as usize, n as usize)?,
+            Instruction::SkpVx { x } => self.op_EX9E_skp_vx(x as usize)?,
+            Instruction::SknpVx { x } => self.op_EXA1_sknp_vx(x as usize)?,
+            Instruction::LdILong => self.op_F000_ld_i_nnnn()?,
+            Instruction::Plane(n) => self.op_FN01_plane(n as usize),
+            Instruction::Audio => self.op_F002_audio()?,
+            Instruction::LdVxDt { x } => self.op_FX07_ld_vx_dt(x as usize),
+            Instruction::LdVxK { x } => self.op_FX0A_ld_vx_key(x as usize),
+            Instruction::LdDtVx { x } => self.op_FX15_ld_dt_vx(x as usize),
+            Instruction::LdStVx { x } => self.op_FX18_ld_st_vx(x as usize),
+            Instruction::AddIVx { x } => self.op_FX1E_add_i_vx(x as usize),
+            Instruction::LdFVx { x } => self.op_FX29_ld_d_vx(x as usize),
+            Instruction::LdHfVx { x } => self.op_FX30_ld_hf_vx(x as usize),
+            Instruction::LdBVx { x } => self.op_FX33_ld_b_vx(x as usize)?,
+            Instruction::PitchVx { x } => self.op_FX3A_pitch_vx(x as usize),
+            Instruction::LdIVx { x } => self.op_FX55_ld_i_vx(x as usize)?,
+            Instruction::LdVxI { x } => self.op_FX65_ld_vx_i(x as usize)?,
+            Instruction::LdRVx { x } => self.op_FX75_ld_r_vx(x as usize),
+            Instruction::LdVxR { x } => self.op_FX85_ld_vx_r(x as usize),
         }
+
+        Ok(())
     }
 
     // ======================== OPCODE INSTRUCTIONS ========================
@@ -200,22 +471,90 @@ impl Emulator {
     // y - A 4-bit value, the upper 4 bits of the low byte of the instruction
     // kk or byte - An 8-bit value, the lowest 8 bits of the instruction
 
-    /// 000 - NOP
-    /// No op, do nothing
+    // 000 - NOP
+    // No op, do nothing
 
     // 0NNN - SYS addr
     // Jump to machine code routine at NNN. Ignored by modern interpreters.
 
+    // Moves the selected planes of the screen by (dx, dy) pixels of the current resolution.
+    // Pixels scrolled in from the edges are cleared.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.screen_size();
+        let planes = self.selected_planes;
+        let previous = self.screen;
+
+        for y in 0..height {
+            for x in 0..width {
+                let src_x = x as isize - dx;
+                let src_y = y as isize - dy;
+                let in_bounds = (0..width as isize).contains(&src_x) && (0..height as isize).contains(&src_y);
+                let scrolled = if in_bounds { previous[(src_y as usize) * width + src_x as usize] } else { 0 };
+
+                let index = y * width + x;
+                self.screen[index] = (previous[index] & !planes) | (scrolled & planes);
+            }
+        }
+    }
+
+    // 00CN - SCD N (SUPER-CHIP)
+    // Scroll display down N lines.
+    fn op_00CN_scd(&mut self, n: usize) {
+        self.scroll(0, n as isize);
+    }
+
+    // 00DN - SCU N (XO-CHIP)
+    // Scroll display up N lines.
+    fn op_00DN_scu(&mut self, n: usize) {
+        self.scroll(0, -(n as isize));
+    }
+
     // 00E0 - CLS
-    // Clear screen
+    // Clear screen (only the selected planes on XO-CHIP)
     fn op_00E0_cls(&mut self) {
-        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        let planes = self.selected_planes;
+        for pixel in self.screen.iter_mut() {
+            *pixel &= !planes;
+        }
     }
 
     // 00EE - RET
     // Return from subroutine (set program counter to the address at the top of the stack).
-    fn op_00EE_ret(&mut self) {
-        self.program_counter = self.pop() as usize;
+    fn op_00EE_ret(&mut self) -> Result<(), Chip8Error> {
+        self.program_counter = self.pop()? as usize;
+        Ok(())
+    }
+
+    // 00FB - SCR (SUPER-CHIP)
+    // Scroll display right 4 pixels.
+    fn op_00FB_scr(&mut self) {
+        self.scroll(4, 0);
+    }
+
+    // 00FC - SCL (SUPER-CHIP)
+    // Scroll display left 4 pixels.
+    fn op_00FC_scl(&mut self) {
+        self.scroll(-4, 0);
+    }
+
+    // 00FD - EXIT (SUPER-CHIP)
+    // Exit the interpreter. The Emulator stops executing until it is reset.
+    fn op_00FD_exit(&mut self) {
+        self.halted = true;
+    }
+
+    // 00FE - LOW (SUPER-CHIP)
+    // Switch to 64x32 low resolution mode, clearing the screen.
+    fn op_00FE_low(&mut self) {
+        self.hires = false;
+        self.screen = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+    }
+
+    // 00FF - HIGH (SUPER-CHIP)
+    // Switch to 128x64 high resolution mode, clearing the screen.
+    fn op_00FF_high(&mut self) {
+        self.hires = true;
+        self.screen = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
     }
 
     // 1NNN - JP
@@ -226,14 +565,17 @@ impl Emulator {
 
     // 2NNN - CALL
     // Call subroutine at address NNN. Push current PC onto top of stack and set PC to NNN.
-    fn op_2NNN_call(&mut self, nnn: usize) {
-        self.push(self.program_counter as u16);
+    fn op_2NNN_call(&mut self, nnn: usize) -> Result<(), Chip8Error> {
+        self.push(self.program_counter as u16)?;
         self.program_counter = nnn;
+        Ok(())
     }
 
+    // On XO-CHIP, skipping over the 4-byte F000 NNNN instruction skips all of it.
     fn skip_if(&mut self, condition: bool) {
         if condition {
-            self.program_counter += 2;
+            let skips_long_load = self.platform.supports_xo_chip() && self.peek_opcode() == Some(0xF000);
+            self.program_counter += if skips_long_load { 4 } else { 2 };
         }
     }
 
@@ -255,6 +597,29 @@ impl Emulator {
         self.skip_if(self.vreg[vi] == self.vreg[vj]);
     }
 
+    // Registers Vx through Vy, in that order (descending if x > y).
+    fn register_range(vi: usize, vj: usize) -> Vec<usize> {
+        if vi <= vj { (vi..=vj).collect() } else { (vj..=vi).rev().collect() }
+    }
+
+    // 5XY2 - LD [I], Vx-Vy (XO-CHIP)
+    // Store Vx through Vy into memory starting at I. Does not change I.
+    fn op_5XY2_ld_i_vx_vy(&mut self, vi: usize, vj: usize) -> Result<(), Chip8Error> {
+        for (offset, reg) in Emulator::register_range(vi, vj).into_iter().enumerate() {
+            self.write_ram(self.ireg + offset, self.vreg[reg])?;
+        }
+        Ok(())
+    }
+
+    // 5XY3 - LD Vx-Vy, [I] (XO-CHIP)
+    // Load Vx through Vy from memory starting at I. Does not change I.
+    fn op_5XY3_ld_vx_vy_i(&mut self, vi: usize, vj: usize) -> Result<(), Chip8Error> {
+        for (offset, reg) in Emulator::register_range(vi, vj).into_iter().enumerate() {
+            self.vreg[reg] = self.read_ram(self.ireg + offset)?;
+        }
+        Ok(())
+    }
+
     // 6XKK - LD Vx, KK
     // Set Vx = KK.
     fn op_6XKK_ld_vx_kk(&mut self, vi: usize, kk: u8) {
@@ -277,19 +642,29 @@ impl Emulator {
         self.vreg[vi] = self.vreg[vj];
     }
 
+    // VF reset quirk: the COSMAC VIP logic ops clobber VF.
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.vreg[0x0F] = 0;
+        }
+    }
+
     // 8XY1 - OR Vx, Vy
     fn op_8XY1_or_vx_vy(&mut self, vi: usize, vj: usize) {
         self.vreg[vi] |= self.vreg[vj];
+        self.reset_vf();
     }
 
     // 8XY2 - AND Vx, Vy
     fn op_8XY2_and_vx_vy(&mut self, vi: usize, vj: usize) {
         self.vreg[vi] &= self.vreg[vj];
+        self.reset_vf();
     }
 
     // 8XY3 - XOR Vx, Vy
     fn op_8XY3_xor_vx_vy(&mut self, vi: usize, vj: usize) {
         self.vreg[vi] ^= self.vreg[vj];
+        self.reset_vf();
     }
 
     // 8XY4 - ADD Vx, Vy
@@ -321,11 +696,12 @@ impl Emulator {
     }
 
     // 8X06 - Shr Vx {, Vy}
-    // Sets Vx = Vx rightshift 1.
+    // Sets Vx = Vx rightshift 1 (or Vy rightshift 1 with the shift quirk).
     // Sets VF = the dropped bit, ie if the least-significant bit of Vx was 1.
-    fn op_8XY6_shr_vx(&mut self, vi: usize) {
-        self.vreg[0x0F] = self.vreg[vi] & 1;
-        self.vreg[vi] >>= 1;
+    fn op_8XY6_shr_vx(&mut self, vi: usize, vj: usize) {
+        let value = if self.quirks.shift_uses_vy { self.vreg[vj] } else { self.vreg[vi] };
+        self.vreg[0x0F] = value & 1;
+        self.vreg[vi] = value >> 1;
     }
 
     // 8XY7 - SUBN Vx, Vy
@@ -342,12 +718,13 @@ impl Emulator {
     }
 
     // 8X0E - SHL Vx {, Vy}
-    // Sets Vx = Vx leftshift 1.
+    // Sets Vx = Vx leftshift 1 (or Vy leftshift 1 with the shift quirk).
     // Sets VF = the dropped bit, ide if the most-significant bit of Vx was 1.
-    fn op_8XYE_shl_vx(&mut self, vi: usize) {
-        self.vreg[0x0F] = (self.vreg[vi] >> 7) & 1;
-        // or self.vreg[0x0F] = (self.vreg[vi] & 0b10000000) >> 7;
-        self.vreg[vi] <<= 1;
+    fn op_8XYE_shl_vx(&mut self, vi: usize, vj: usize) {
+        let value = if self.quirks.shift_uses_vy { self.vreg[vj] } else { self.vreg[vi] };
+        self.vreg[0x0F] = (value >> 7) & 1;
+        // or self.vreg[0x0F] = (value & 0b10000000) >> 7;
+        self.vreg[vi] = value << 1;
     }
 
     // 9XY0 - SNE Vx, Vy
@@ -364,62 +741,140 @@ impl Emulator {
 
     // BNNN - JMP V0, NNN
     // Set PC = V0 + NNN (address)
-    fn op_BNNN_jmp_v0_nnn(&mut self, nnn: usize) {
-        self.program_counter = (self.vreg[0] as usize) + nnn;
+    // With the jump quirk this is BXNN - JMP Vx, XNN instead (CHIP-48/SUPER-CHIP).
+    fn op_BNNN_jmp_v0_nnn(&mut self, vi: usize, nnn: usize) {
+        let offset_reg = if self.quirks.jump_uses_vx { vi } else { 0 };
+        self.program_counter = (self.vreg[offset_reg] as usize) + nnn;
     }
 
     // CXKK - RND Vx, KK
     // Set Vx = rand() AND KK
     fn op_CXKK_ld_vx_rand_and_kk(&mut self, vi: usize, kk: u8) {
-        let rng: u8 = rand::thread_rng().gen();
-        self.vreg[vi] = rng & kk;
+        self.vreg[vi] = self.rng.next_byte() & kk;
     }
 
     // DXYN - DRW Vx, Vy, N
     // Starting at address stored in I, reads N bytes from memory,
-    // and draws them as sprites on screen at (Vx, Vy) (wrapping).
-    // Sets VF = if pixels are erased.
-    fn op_DXYN_drw(&mut self, vi: usize, vj: usize, n: usize) {
-        let x_coord = self.vreg[vi] as usize;
-        let y_coord = self.vreg[vj] as usize;
+    // and draws them as sprites on screen at (Vx, Vy) (wrapping, or clipping with the clipping quirk).
+    // DXY0 - DRW Vx, Vy, 0 (SUPER-CHIP)
+    // Draws a 16x16 sprite instead, stored as 32 bytes (2 per row).
+    // On XO-CHIP the sprite is drawn to each selected plane in turn, with the data for each plane stored one after another.
+    // Sets VF = if pixels are erased, or with the row count quirk in high resolution mode, the
+    // number of sprite rows that erased pixels or were clipped by the bottom edge.
+    // With the display wait quirk, execution then stalls until the next tick_timers, as the
+    // original interpreter waited for the vertical blank, so at most 60 sprites are drawn a second.
+    fn op_DXYN_drw(&mut self, vi: usize, vj: usize, n: usize) -> Result<(), Chip8Error> {
+        let (width, height) = self.screen_size();
+        let (sprite_width, sprite_height) = if n == 0 && self.platform.supports_super_chip() {
+            (16, 16)
+        } else {
+            (8, n)
+        };
+        let bytes_per_row = sprite_width / 8;
+
+        let x_coord = self.vreg[vi] as usize % width;
+        let y_coord = self.vreg[vj] as usize % height;
         let mut flipped = false;
+        let mut collided_rows = 0;
+        let mut sprite_addr = self.ireg;
+
+        for plane in [0b01, 0b10] {
+            if self.selected_planes & plane == 0 {
+                continue;
+            }
 
-        for byte in 0..n {
-            let y = (y_coord + byte) % SCREEN_HEIGHT;
-            let pixels = self.ram[self.ireg + byte];
+            for row in 0..sprite_height {
+                if self.quirks.clipping && y_coord + row >= height {
+                    collided_rows += sprite_height - row;
+                    break;
+                }
+                let y = (y_coord + row) % height;
+                let mut row_collided = false;
+
+                // Left-align the row in 16 bits, so 8 and 16 pixel wide sprites are drawn alike.
+                let row_addr = sprite_addr + row * bytes_per_row;
+                let mut pixels = (self.read_ram(row_addr)? as u16) << 8;
+                if bytes_per_row == 2 {
+                    pixels |= self.read_ram(row_addr + 1)? as u16;
+                }
 
-            for bit in 0..8 {
-                let x = (x_coord + bit) % SCREEN_WIDTH;
-                let index = y * SCREEN_WIDTH + x;
+                for bit in 0..sprite_width {
+                    if self.quirks.clipping && x_coord + bit >= width {
+                        break;
+                    }
+                    let x = (x_coord + bit) % width;
+                    let index = y * width + x;
 
-                let color = pixels & (0b1000_0000 >> bit);
+                    let color = pixels & (0b1000_0000_0000_0000 >> bit);
 
-                // v1
-                if color != 0 {
-                    flipped |= self.screen[index];
-                    self.screen[index] ^= true; // (Set VF later at end)
-                }
+                    // v1
+                    if color != 0 {
+                        row_collided |= self.screen[index] & plane != 0;
+                        self.screen[index] ^= plane; // (Set VF later at end)
+                    }
 
-                // or v2...?
-                // self.vreg[0x0F] |= color * self.screen[index]; // Set VF by OR-ing with color * pixel?
-                // self.screen[index] ^= color; // Draw pixel
+                    // or v2...?
+                    // self.vreg[0x0F] |= color * self.screen[index]; // Set VF by OR-ing with color * pixel?
+                    // self.screen[index] ^= color; // Draw pixel
+                }
+                if row_collided {
+                    flipped = true;
+                    collided_rows += 1;
+                }
             }
+
+            sprite_addr += sprite_height * bytes_per_row;
         }
 
         // v1
-        self.vreg[0x0F] = if flipped { 1 } else { 0 };
+        self.vreg[0x0F] = if self.quirks.vf_row_count && self.hires {
+            collided_rows.min(u8::MAX as usize) as u8
+        } else if flipped {
+            1
+        } else {
+            0
+        };
+
+        if self.quirks.display_wait {
+            self.waiting_for_vblank = true;
+        }
+        Ok(())
     }
 
     // EX9E - SKP Vx
     // Skip next instruction if key with value stored in Vx is pressed.
-    fn op_EX9E_skp_vx(&mut self, vi: usize) {
-        self.skip_if(self.keys[self.vreg[vi] as usize]);
+    fn op_EX9E_skp_vx(&mut self, vi: usize) -> Result<(), Chip8Error> {
+        self.skip_if(self.key_state(self.vreg[vi] as usize)?);
+        Ok(())
     }
 
     // EXA1 - SKNP
     // Skip next instruction if key with the value stored in Vx is not pressed.
-    fn op_EXA1_sknp_vx(&mut self, vi: usize) {
-        self.skip_if(!self.keys[self.vreg[vi] as usize]);
+    fn op_EXA1_sknp_vx(&mut self, vi: usize) -> Result<(), Chip8Error> {
+        self.skip_if(!self.key_state(self.vreg[vi] as usize)?);
+        Ok(())
+    }
+
+    // F000 NNNN - LD I, NNNN (XO-CHIP)
+    // Set I = the 16-bit address stored in the 2 bytes following the opcode.
+    fn op_F000_ld_i_nnnn(&mut self) -> Result<(), Chip8Error> {
+        self.ireg = self.fetch_opcode()? as usize;
+        Ok(())
+    }
+
+    // FN01 - PLANE N (XO-CHIP)
+    // Select the bitplanes (bitmask, 0-3) that drawing, clearing and scrolling act on.
+    fn op_FN01_plane(&mut self, n: usize) {
+        self.selected_planes = (n & 0b11) as u8;
+    }
+
+    // F002 - AUDIO (XO-CHIP)
+    // Load the 16-byte audio pattern buffer from memory starting at I.
+    fn op_F002_audio(&mut self) -> Result<(), Chip8Error> {
+        for i in 0..AUDIO_PATTERN_SIZE {
+            self.audio_pattern[i] = self.read_ram(self.ireg + i)?;
+        }
+        Ok(())
     }
 
     // FX07 - LD Vx, DT
@@ -429,8 +884,40 @@ impl Emulator {
     }
 
     // FX0A - LD Vx, Key
-    // Wait for a key press, and then store the pressed key value in Vx.
+    // Wait for a key to be pressed and released, and then store its value in Vx.
+    // Keys already held when the wait begins only count once pressed again.
+    // With the key_wait_on_press quirk, a key being held is enough.
     fn op_FX0A_ld_vx_key(&mut self, vi: usize) {
+        if self.quirks.key_wait_on_press {
+            self.wait_for_key_press(vi);
+            return;
+        }
+
+        let mut key_wait = self.key_wait.unwrap_or(KeyWait { previous: self.keys, pressed: [false; NUM_KEYS] });
+        let mut released = None;
+        for key in 0..NUM_KEYS {
+            key_wait.pressed[key] |= self.keys[key] && !key_wait.previous[key];
+            if key_wait.pressed[key] && !self.keys[key] && released.is_none() {
+                released = Some(key);
+            }
+        }
+        key_wait.previous = self.keys;
+
+        // Block/wait until a key is released by repeating the previous opcode.
+        match released {
+            Some(key) => {
+                self.vreg[vi] = key as u8;
+                self.key_wait = None;
+            },
+            None => {
+                self.key_wait = Some(key_wait);
+                self.program_counter -= 2;
+            }
+        }
+    }
+
+    // FX0A with the key_wait_on_press quirk: done as soon as any key is held.
+    fn wait_for_key_press(&mut self, vi: usize) {
         let mut pressed = false;
 
         for key in 0..self.keys.len() {
@@ -472,6 +959,18 @@ impl Emulator {
         self.ireg = (self.vreg[vi] as usize) * 5; // each font occupies 5 bytes
     }
 
+    // FX30 - LD HF, Vx (SUPER-CHIP)
+    // Set I = address of big 8x10 font sprite for digit Vx.
+    fn op_FX30_ld_hf_vx(&mut self, vi: usize) {
+        self.ireg = BIG_FONTSET_ADDR + (self.vreg[vi] as usize) * 10; // each big font occupies 10 bytes
+    }
+
+    // FX3A - PITCH Vx (XO-CHIP)
+    // Set the audio pattern playback rate to 4000*2^((Vx-64)/48) bits per second.
+    fn op_FX3A_pitch_vx(&mut self, vi: usize) {
+        self.pitch = self.vreg[vi];
+    }
+
     // BCD = Binary-Coded Decimal
     fn easy_to_read_bcd(x: f32) -> [u8; 3] {
         let hundreds = (x / 100.0) as u8;
@@ -482,31 +981,53 @@ impl Emulator {
 
     // FX33 - LD B, Vx
     // The interpreter takes the decimal value of Vx, and stores each digit in I.
-    fn op_FX33_ld_b_vx(&mut self, vi: usize) {
+    fn op_FX33_ld_b_vx(&mut self, vi: usize) -> Result<(), Chip8Error> {
         let vx = self.vreg[vi] as f32;
 
         let [hundreds, tens, ones] = Emulator::easy_to_read_bcd(vx);
 
-        self.ram[self.ireg as usize] = hundreds;
-        self.ram[(self.ireg + 1) as usize] = tens;
-        self.ram[(self.ireg + 2) as usize] = ones;
+        self.write_ram(self.ireg, hundreds)?;
+        self.write_ram(self.ireg + 1, tens)?;
+        self.write_ram(self.ireg + 2, ones)
     }
 
     // FX55 - LD [I], Vx
     // Load V0 through VX into memory at address stored in I register.
-    fn op_FX55_ld_i_vx(&mut self, n: usize) {
+    // With the load/store quirk, I is left at I + X + 1.
+    fn op_FX55_ld_i_vx(&mut self, n: usize) -> Result<(), Chip8Error> {
         let start_address = self.ireg;
         for i in 0..=n {
-            self.ram[start_address + i] = self.vreg[i];
+            self.write_ram(start_address + i, self.vreg[i])?;
         }
+        if self.quirks.load_store_increments_i {
+            self.ireg += n + 1;
+        }
+        Ok(())
     }
 
     // FX65 - LD Vx, [I]
     // Load values from memory starting at address I into registers V0 thru Vx
-    fn op_FX65_ld_vx_i(&mut self, n: usize) {
+    // With the load/store quirk, I is left at I + X + 1.
+    fn op_FX65_ld_vx_i(&mut self, n: usize) -> Result<(), Chip8Error> {
         let start_address = self.ireg;
         for i in 0..=n {
-            self.vreg[i] = self.ram[start_address + i]
+            self.vreg[i] = self.read_ram(start_address + i)?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.ireg += n + 1;
         }
+        Ok(())
+    }
+
+    // FX75 - LD R, Vx (SUPER-CHIP)
+    // Store V0 through Vx in the RPL user flags.
+    fn op_FX75_ld_r_vx(&mut self, n: usize) {
+        self.rpl_flags[..=n].copy_from_slice(&self.vreg[..=n]);
+    }
+
+    // FX85 - LD Vx, R (SUPER-CHIP)
+    // Load V0 through Vx from the RPL user flags.
+    fn op_FX85_ld_vx_r(&mut self, n: usize) {
+        self.vreg[..=n].copy_from_slice(&self.rpl_flags[..=n]);
     }
 }
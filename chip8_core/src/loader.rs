@@ -0,0 +1,49 @@
+// Loader: sets up an Emulator for a ROM the way the frontends do, picking the platform
+// and quirks from the ROM's file name and the user's settings.
+
+use crate::{Chip8Error, Emulator, Platform, Quirks};
+
+// How the user asked a ROM to be run. Anything left unset is picked from the ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RomSettings {
+    // None to pick the platform from the ROM's file extension.
+    pub platform: Option<Platform>,
+    // None for the platform's own quirks.
+    pub quirks: Option<Quirks>,
+    // Overrides the display wait quirk of the quirks.
+    pub display_wait: Option<bool>,
+    // FX0A takes a held key, instead of waiting for it to be released.
+    pub key_wait_on_press: bool,
+    // Seed for the random numbers of CXKK, or None for random ones.
+    pub seed: Option<u64>,
+}
+
+// Picks the platform from the ROM's file extension, e.g. `.sc8` for SUPER-CHIP or `.xo8` for XO-CHIP.
+pub fn platform_for_rom(rom_path: &str) -> Platform {
+    match rom_path.rsplit('.').next() {
+        Some("sc8") => Platform::SuperChip,
+        Some("xo8") => Platform::XoChip,
+        _ => Platform::Chip8
+    }
+}
+
+// An Emulator with the ROM at `rom_path`, whose contents are `rom`, loaded.
+pub fn load_rom(rom_path: &str, rom: &[u8], settings: &RomSettings) -> Result<Emulator, Chip8Error> {
+    let platform = settings.platform.unwrap_or_else(|| platform_for_rom(rom_path));
+    let mut chip8 = match (platform, settings.quirks) {
+        (Platform::Chip8, None) => Emulator::new(),
+        (platform, quirks) => Emulator::with_platform(platform, quirks.unwrap_or(platform.default_quirks()))
+    };
+    if let Some(display_wait) = settings.display_wait {
+        chip8.set_quirks(Quirks { display_wait, ..chip8.quirks() });
+    }
+    if settings.key_wait_on_press {
+        chip8.set_quirks(Quirks { key_wait_on_press: true, ..chip8.quirks() });
+    }
+    if let Some(seed) = settings.seed {
+        chip8.set_seed(seed);
+    }
+
+    chip8.load_data(rom)?;
+    Ok(chip8)
+}
@@ -0,0 +1,319 @@
+// Movies: recordings of keypad input that replay a session exactly.
+//
+// A movie holds the save state the recording started from (which includes the ROM and the
+// RNG state), the timing and speed of the Scheduler that ran it, and every keypress made
+// after it, stamped with the frame it happened in. Frames are counted by tick_timers.
+// Replaying the keypresses from the start state with the same timing reproduces the session,
+// which is checked against a hash of the screen at the end of the last recorded frame.
+//
+// File layout (multi-byte values are Big Endian):
+//   magic    4 bytes  "C8MV"
+//   version  u16
+//   length   u32      length of the payload
+//   payload           see Movie::to_bytes
+//   checksum u32      FNV-1a of the payload
+
+use std::fmt;
+
+use crate::rewind::{apply_delta, encode_delta};
+use crate::state::{checksum, StateReader, StateWriter};
+use crate::{Chip8Error, Emulator, Scheduler, StateError, Timing, MAX_SPEED, MIN_SPEED};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+// Version 2 added the timing and speed. Version 1 movies don't say how many instructions
+// their frames ran, so they can't be replayed reliably.
+const VERSION: u16 = 2;
+// Timing byte of the payload, followed by the instructions per second for TIMING_CPU_HZ.
+const TIMING_CPU_HZ: u8 = 0;
+const TIMING_COSMAC_VIP: u8 = 1;
+// Key byte of an event: the key index, with this bit set for a press.
+const PRESSED_BIT: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieError {
+    // Data does not start with the movie magic header.
+    BadMagic,
+    // Movie was written by a newer (or unknown) version of the format.
+    UnsupportedVersion(u16),
+    // Data ends before the movie does.
+    Truncated,
+    // Payload does not match its checksum.
+    ChecksumMismatch,
+    // Payload or its start state holds invalid values.
+    Corrupt,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion(version) => write!(f, "unsupported movie version {}", version),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::ChecksumMismatch => write!(f, "movie checksum does not match"),
+            MovieError::Corrupt => write!(f, "movie is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(err: StateError) -> Self {
+        match err {
+            StateError::BadMagic => MovieError::BadMagic,
+            StateError::UnsupportedVersion(version) => MovieError::UnsupportedVersion(version),
+            StateError::Truncated => MovieError::Truncated,
+            StateError::ChecksumMismatch => MovieError::ChecksumMismatch,
+            StateError::Corrupt => MovieError::Corrupt,
+        }
+    }
+}
+
+// A keypress, made during the given frame before any of that frame's instructions ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieEvent {
+    pub frame: u32,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    start_state: Vec<u8>,
+    timing: Timing,
+    speed: f64,
+    events: Vec<MovieEvent>,
+    frames: u32,
+    screen_hash: u32,
+}
+
+// Movie being recorded, kept in the Emulator so every keypress is seen.
+#[derive(Debug, Clone)]
+pub(crate) struct Recording {
+    start_state: Vec<u8>,
+    timing: Timing,
+    speed: f64,
+    events: Vec<MovieEvent>,
+    frame: u32,
+    // Hash of the screen at the end of the last frame.
+    screen_hash: u32,
+}
+
+impl Recording {
+    pub(crate) fn keypress(&mut self, key: usize, pressed: bool) {
+        self.events.push(MovieEvent { frame: self.frame, key: key as u8, pressed });
+    }
+
+    pub(crate) fn end_frame(&mut self, screen_hash: u32) {
+        self.frame += 1;
+        self.screen_hash = screen_hash;
+    }
+}
+
+impl Movie {
+    pub fn events(&self) -> &[MovieEvent] {
+        &self.events
+    }
+
+    // Number of frames recorded.
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    // Hash of the screen at the end of the last frame, see Emulator::screen_hash.
+    pub fn screen_hash(&self) -> u32 {
+        self.screen_hash
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    // A Scheduler that runs the frames of the movie as they were recorded.
+    pub fn scheduler(&self) -> Scheduler {
+        let mut scheduler = Scheduler::with_timing(self.timing);
+        scheduler.set_speed(self.speed);
+        scheduler
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // Save states are mostly zeros, so the start state is stored as a delta against an all-zero state.
+        let mut payload = StateWriter { data: Vec::new() };
+        payload.u32(self.start_state.len() as u32);
+        let start_state = encode_delta(&self.start_state, &vec![0; self.start_state.len()]);
+        payload.u32(start_state.len() as u32);
+        payload.bytes(&start_state);
+        match self.timing {
+            Timing::CpuHz(cpu_hz) => {
+                payload.u8(TIMING_CPU_HZ);
+                payload.u32(cpu_hz);
+            },
+            Timing::CosmacVip => payload.u8(TIMING_COSMAC_VIP)
+        }
+        payload.u64(self.speed.to_bits());
+        payload.u32(self.frames);
+        payload.u32(self.screen_hash);
+        payload.u32(self.events.len() as u32);
+        for event in &self.events {
+            payload.u32(event.frame);
+            payload.u8(event.key | if event.pressed { PRESSED_BIT } else { 0 });
+        }
+
+        let mut movie = StateWriter { data: Vec::new() };
+        movie.bytes(MAGIC);
+        movie.u16(VERSION);
+        movie.u32(payload.data.len() as u32);
+        movie.bytes(&payload.data);
+        movie.u32(checksum(&payload.data));
+        movie.data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        let mut header = StateReader { data, pos: 0 };
+        if header.bytes(MAGIC.len()).map_err(|_| MovieError::BadMagic)? != MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = header.u16()?;
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let length = header.u32()? as usize;
+        let payload = header.bytes(length)?;
+        if header.u32()? != checksum(payload) {
+            return Err(MovieError::ChecksumMismatch);
+        }
+
+        let mut input = StateReader { data: payload, pos: 0 };
+        let mut start_state = vec![0; input.u32()? as usize];
+        let delta_length = input.u32()? as usize;
+        if !apply_delta(&mut start_state, input.bytes(delta_length)?) {
+            return Err(MovieError::Corrupt);
+        }
+        let timing = match input.u8()? {
+            TIMING_CPU_HZ => Timing::CpuHz(input.u32()?),
+            TIMING_COSMAC_VIP => Timing::CosmacVip,
+            _ => return Err(MovieError::Corrupt)
+        };
+        let speed = f64::from_bits(input.u64()?);
+        if !(MIN_SPEED..=MAX_SPEED).contains(&speed) {
+            return Err(MovieError::Corrupt);
+        }
+        let frames = input.u32()?;
+        let screen_hash = input.u32()?;
+        let event_count = input.u32()? as usize;
+        let mut events = Vec::new();
+        for _ in 0..event_count {
+            let frame = input.u32()?;
+            let key = input.u8()?;
+            let out_of_order = events.last().is_some_and(|last: &MovieEvent| last.frame > frame);
+            if frame >= frames || out_of_order || key & !PRESSED_BIT >= 16 {
+                return Err(MovieError::Corrupt);
+            }
+            events.push(MovieEvent { frame, key: key & !PRESSED_BIT, pressed: key & PRESSED_BIT != 0 });
+        }
+        if input.pos != payload.len() {
+            return Err(MovieError::Corrupt);
+        }
+
+        Ok(Movie { start_state, timing, speed, events, frames, screen_hash })
+    }
+}
+
+// Replays a movie, one frame at a time.
+pub struct MoviePlayer {
+    movie: Movie,
+    next_event: usize,
+    frame: u32,
+}
+
+impl MoviePlayer {
+    // Restores the movie's start state into the Emulator and rewinds to the first frame.
+    pub fn start(movie: Movie, emulator: &mut Emulator) -> Result<MoviePlayer, MovieError> {
+        emulator.load_state(&movie.start_state)?;
+        Ok(MoviePlayer { movie, next_event: 0, frame: 0 })
+    }
+
+    // Presses and releases the keys of the next frame. Call once per frame, before running
+    // the frame's instructions and tick_timers, like a frontend handles real input.
+    pub fn play_frame(&mut self, emulator: &mut Emulator) -> Result<(), Chip8Error> {
+        if self.is_finished() {
+            return Ok(());
+        }
+
+        while let Some(event) = self.movie.events.get(self.next_event).filter(|event| event.frame == self.frame) {
+            emulator.keypress(event.key as usize, event.pressed)?;
+            self.next_event += 1;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    // True once every recorded frame has been played.
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+
+    // Number of frames played so far.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    // After the last frame: true if the Emulator ended up with the same screen as the recording.
+    pub fn verify(&self, emulator: &Emulator) -> bool {
+        self.is_finished() && emulator.screen_hash() == self.movie.screen_hash
+    }
+}
+
+impl Emulator {
+    // Starts recording keypresses from the current state, for frames run by the scheduler.
+    // Restarts any recording in progress. The scheduler is reset, so its frames run the same
+    // instructions as those of the Scheduler the movie is played back with.
+    pub fn start_recording(&mut self, scheduler: &mut Scheduler) {
+        scheduler.reset();
+        self.recording = Some(Recording {
+            start_state: self.save_state(),
+            timing: scheduler.timing(),
+            speed: scheduler.speed(),
+            events: Vec::new(),
+            frame: 0,
+            screen_hash: self.screen_hash()
+        });
+    }
+
+    // Stops recording, returning the movie if one was being recorded.
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        let mut recording = self.recording.take()?;
+        // Keys pressed after the last completed frame had no effect yet.
+        recording.events.retain(|event| event.frame < recording.frame);
+        Some(Movie {
+            start_state: recording.start_state,
+            timing: recording.timing,
+            speed: recording.speed,
+            events: recording.events,
+            frames: recording.frame,
+            screen_hash: recording.screen_hash
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // FNV-1a hash of the resolution and contents of the screen.
+    pub fn screen_hash(&self) -> u32 {
+        let display = self.get_display();
+        let mut data = Vec::with_capacity(2 + display.pixels.len());
+        data.push(display.width as u8);
+        data.push(display.height as u8);
+        data.extend_from_slice(display.pixels);
+        checksum(&data)
+    }
+}
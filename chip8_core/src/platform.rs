@@ -0,0 +1,45 @@
+use crate::{Quirks, RAM_SIZE, XO_RAM_SIZE};
+
+// Instruction set extension the Emulator runs.
+// Each platform accepts the opcodes of the ones listed before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub const NAMES: [&'static str; 3] = ["chip8", "schip", "xochip"];
+
+    pub fn from_name(name: &str) -> Option<Platform> {
+        match name.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Some(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" => Some(Platform::SuperChip),
+            "xochip" | "xo-chip" => Some(Platform::XoChip),
+            _ => None
+        }
+    }
+
+    // Quirks the platform's original interpreter had.
+    pub fn default_quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::COSMAC_VIP,
+            Platform::SuperChip => Quirks::SUPER_CHIP,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    pub fn ram_size(&self) -> usize {
+        if self.supports_xo_chip() { XO_RAM_SIZE } else { RAM_SIZE }
+    }
+
+    pub fn supports_super_chip(&self) -> bool {
+        *self >= Platform::SuperChip
+    }
+
+    pub fn supports_xo_chip(&self) -> bool {
+        *self >= Platform::XoChip
+    }
+}
@@ -0,0 +1,87 @@
+// Interpretations of the CHIP-8 instructions that differ between platforms.
+// The default profile keeps this emulator's original behaviour, except that FX0A waits for
+// a key to be released, as every platform's did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    // 8XY6/8XYE shift Vy into Vx, instead of shifting Vx in place.
+    pub shift_uses_vy: bool,
+    // FX55/FX65 leave I pointing just past the last register stored/loaded.
+    pub load_store_increments_i: bool,
+    // BNNN jumps to XNN + Vx, instead of NNN + V0.
+    pub jump_uses_vx: bool,
+    // 8XY1/8XY2/8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    // DXYN clips sprites at the screen edges, instead of wrapping them around.
+    pub clipping: bool,
+    // DXYN waits for the next vertical blank (tick_timers) before execution continues.
+    pub display_wait: bool,
+    // In high resolution mode, DXYN sets VF to the number of sprite rows that erased pixels
+    // or were clipped by the bottom edge, instead of 1 (SUPER-CHIP 1.1).
+    pub vf_row_count: bool,
+    // FX0A completes as soon as a key is held, instead of when a key pressed during the wait
+    // is released.
+    pub key_wait_on_press: bool,
+}
+
+impl Quirks {
+    // Original COSMAC VIP interpreter (1977).
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        vf_reset: true,
+        clipping: true,
+        display_wait: true,
+        vf_row_count: false,
+        key_wait_on_press: false,
+    };
+
+    // CHIP-48 for the HP-48 calculators (1990).
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: true,
+        jump_uses_vx: true,
+        vf_reset: false,
+        clipping: true,
+        display_wait: false,
+        vf_row_count: false,
+        key_wait_on_press: false,
+    };
+
+    // SUPER-CHIP 1.1 (1991).
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        jump_uses_vx: true,
+        vf_reset: false,
+        clipping: true,
+        display_wait: false,
+        vf_row_count: true,
+        key_wait_on_press: false,
+    };
+
+    // XO-CHIP, as implemented by Octo (2014).
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        vf_reset: false,
+        clipping: false,
+        display_wait: false,
+        vf_row_count: false,
+        key_wait_on_press: false,
+    };
+
+    // Names accepted by from_preset_name, in the same order as the presets above.
+    pub const PRESET_NAMES: [&'static str; 4] = ["vip", "chip48", "schip", "xochip"];
+
+    pub fn from_preset_name(name: &str) -> Option<Quirks> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac-vip" | "chip8" => Some(Quirks::COSMAC_VIP),
+            "chip48" | "chip-48" => Some(Quirks::CHIP_48),
+            "schip" | "superchip" | "super-chip" => Some(Quirks::SUPER_CHIP),
+            "xochip" | "xo-chip" | "octo" => Some(Quirks::XO_CHIP),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// Random numbers for CXKK (RND).
+//
+// The Emulator draws from a RandomSource instead of a global RNG, so that a run started
+// from the same seed (or save state) with the same input always plays out the same way.
+
+// Source of the random bytes CXKK masks with KK.
+pub trait RandomSource: Send {
+    fn next_byte(&mut self) -> u8;
+
+    // Internal state, stored in save states. Sources that can't be restored return an empty Vec.
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    // Restores a state produced by save_state. Returns false if the state is not valid for this source.
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        state.is_empty()
+    }
+
+    // Emulators are Clone, so their RandomSource has to be too.
+    fn clone_box(&self) -> Box<dyn RandomSource>;
+}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// Default RandomSource: a xorshift64* generator. Small, fast and its whole state is one u64.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on 0, so the seed is scrambled (SplitMix64) into a non-zero state.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        SeededRandom { state: if z == 0 { 1 } else { z } }
+    }
+
+    // Seeded from the OS, for runs that don't need to be reproducible.
+    pub fn from_entropy() -> Self {
+        SeededRandom::new(rand::random())
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        self.state.to_be_bytes().to_vec()
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        match state.try_into().map(u64::from_be_bytes) {
+            Ok(value) if value != 0 => {
+                self.state = value;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,171 @@
+// Rewind: a ring buffer of recent save states for stepping gameplay backwards.
+//
+// Only the newest state is kept in full. Every older frame is stored as the delta
+// between it and the frame after it: the XOR of the two states, with the runs of
+// unchanged (zero) bytes squeezed out. A frame usually only touches a handful of
+// bytes of the machine state, so most deltas are tiny compared to a full state.
+
+use std::collections::VecDeque;
+
+use crate::Emulator;
+
+pub struct RewindBuffer {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>
+}
+
+// Delta encoding: repeated (skip: u16, len: u16, len bytes) chunks, where skip counts
+// the unchanged bytes before the chunk and the bytes are the XOR of the two states.
+// XOR works both ways, so the same delta turns either state into the other.
+pub(crate) fn encode_delta(a: &[u8], b: &[u8]) -> Vec<u8> {
+    const MAX_CHUNK: usize = u16::MAX as usize;
+    let mut delta = Vec::new();
+    let mut pos = 0;
+
+    while let Some(start) = (pos..b.len()).find(|&i| a[i] != b[i]) {
+        let mut skip = start - pos;
+        while skip > MAX_CHUNK {
+            delta.extend_from_slice(&(MAX_CHUNK as u16).to_be_bytes());
+            delta.extend_from_slice(&0u16.to_be_bytes());
+            skip -= MAX_CHUNK;
+        }
+
+        let end = (start..b.len()).find(|&i| a[i] == b[i]).unwrap_or(b.len()).min(start + MAX_CHUNK);
+        delta.extend_from_slice(&(skip as u16).to_be_bytes());
+        delta.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        delta.extend((start..end).map(|i| a[i] ^ b[i]));
+        pos = end;
+    }
+
+    delta
+}
+
+// Returns false if the delta doesn't fit the state; the state may then be partially changed.
+pub(crate) fn apply_delta(state: &mut [u8], delta: &[u8]) -> bool {
+    let mut pos = 0;
+    let mut chunks = delta;
+
+    while chunks.len() >= 4 {
+        let skip = u16::from_be_bytes([chunks[0], chunks[1]]) as usize;
+        let len = u16::from_be_bytes([chunks[2], chunks[3]]) as usize;
+        pos += skip;
+        let (Some(bytes), Some(diffs)) = (state.get_mut(pos..pos + len), chunks.get(4..4 + len)) else {
+            return false;
+        };
+        for (byte, diff) in bytes.iter_mut().zip(diffs) {
+            *byte ^= diff;
+        }
+        pos += len;
+        chunks = &chunks[4 + len..];
+    }
+
+    chunks.is_empty()
+}
+
+impl RewindBuffer {
+    // Keeps up to `capacity` frames to rewind through, e.g. 600 for 10 seconds at 60 fps.
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            latest: None,
+            deltas: VecDeque::with_capacity(capacity)
+        }
+    }
+
+    // Records the current state of the Emulator as the newest frame.
+    pub fn push(&mut self, emulator: &Emulator) {
+        let state = emulator.save_state();
+
+        if let Some(latest) = self.latest.take() {
+            // A state of a different size (e.g. another platform was loaded) can't be diffed against.
+            if latest.len() == state.len() {
+                self.deltas.push_back(encode_delta(&state, &latest));
+                while self.deltas.len() > self.capacity {
+                    self.deltas.pop_front();
+                }
+            } else {
+                self.deltas.clear();
+            }
+        }
+
+        self.latest = Some(state);
+    }
+
+    // Steps the Emulator back to the frame before the newest one, and drops the newest.
+    // The keys stay as they are held now, not as they were then.
+    // Returns false when there is nothing left to rewind. A frame that can't be restored leaves
+    // the Emulator as it is, and empties the buffer, as the frames before it build on it.
+    pub fn rewind(&mut self, emulator: &mut Emulator) -> bool {
+        let (Some(latest), Some(delta)) = (self.latest.as_mut(), self.deltas.pop_back()) else {
+            return false;
+        };
+
+        let mut state = latest.clone();
+        let keys = emulator.keys;
+        if !apply_delta(&mut state, &delta) || emulator.load_state(&state).is_err() {
+            self.clear();
+            return false;
+        }
+        emulator.keys = keys;
+        *latest = state;
+        true
+    }
+
+    // Number of frames that can be rewound.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+
+    // Approximate number of bytes held by the buffer.
+    pub fn memory_usage(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ADD V0, 1; JP 200
+    fn counter() -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_data(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        emulator
+    }
+
+    #[test]
+    fn frame_that_cant_be_restored_empties_the_buffer() {
+        let mut emulator = counter();
+        let mut buffer = RewindBuffer::new(10);
+        for _ in 0..4 {
+            emulator.tick().unwrap();
+            buffer.push(&emulator);
+        }
+        let before = emulator.save_state();
+
+        // A chunk that applies, then one past the end of the state.
+        let delta = buffer.deltas.back_mut().unwrap();
+        delta.extend([0, 0, 0, 1, 0xFF, 0xFF, 0xFF, 0, 1, 0xFF]);
+        assert!(!buffer.rewind(&mut emulator));
+        assert_eq!(emulator.save_state(), before);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.memory_usage(), 0);
+
+        // The buffer starts over from the next frame.
+        buffer.push(&emulator);
+        emulator.tick().unwrap();
+        buffer.push(&emulator);
+        assert!(buffer.rewind(&mut emulator));
+        assert_eq!(emulator.save_state(), before);
+    }
+}
@@ -0,0 +1,66 @@
+// Runner: runs an Emulator frame by frame without a display, as the frontends do for
+// headless runs, with scripted keys or a movie as input.
+
+use crate::{Chip8Error, Emulator, MoviePlayer, Scheduler, StopReason};
+
+// A hex key held down from `frame` for `hold` frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyPress {
+    pub frame: usize,
+    pub key: usize,
+    pub hold: usize,
+}
+
+// Why a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    // Every frame ran.
+    FramesElapsed,
+    // The debugger paused the Emulator, e.g. at a breakpoint.
+    Stopped(StopReason),
+    // 00FD EXIT.
+    Halted,
+    Crashed(Chip8Error),
+}
+
+// Presses and releases the keys scripted for a frame.
+pub fn press_keys(chip8: &mut Emulator, frame: usize, keys: &[KeyPress]) -> Result<(), Chip8Error> {
+    for press in keys {
+        if frame == press.frame {
+            chip8.keypress(press.key, true)?;
+        } else if frame == press.frame + press.hold {
+            chip8.keypress(press.key, false)?;
+        }
+    }
+    Ok(())
+}
+
+// Runs up to `frames` frames: each presses the frame's keys (or plays the movie's), runs the
+// frame's instructions, calls `after_frame` and ticks the timers. A movie being played replaces
+// the frame limit with its own length.
+// Returns why the run ended and the frame it ended in (`frames` if every frame ran). Once the
+// Emulator stops, the rest of the frame's instructions don't run and its timers don't tick.
+pub fn run_frames(chip8: &mut Emulator, scheduler: &mut Scheduler, frames: usize, keys: &[KeyPress],
+                  mut player: Option<&mut MoviePlayer>, mut after_frame: impl FnMut(&mut Emulator)) -> (RunOutcome, usize) {
+    let frames = player.as_ref().map_or(frames, |player| player.movie().frames() as usize);
+    for frame in 0..frames {
+        let input = match player.as_mut() {
+            Some(player) => player.play_frame(chip8),
+            None => press_keys(chip8, frame, keys)
+        };
+        if let Err(err) = input.and_then(|()| scheduler.run_frame(chip8)) {
+            return (RunOutcome::Crashed(err), frame);
+        }
+        after_frame(chip8);
+
+        if let Some(reason) = chip8.stop_reason() {
+            return (RunOutcome::Stopped(reason), frame);
+        }
+        if chip8.is_halted() {
+            return (RunOutcome::Halted, frame);
+        }
+        chip8.tick_timers();
+    }
+
+    (RunOutcome::FramesElapsed, frames)
+}
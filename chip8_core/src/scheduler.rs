@@ -0,0 +1,118 @@
+// Scheduler: paces the Emulator against a real-time clock, whatever rate the frontend's
+// loop runs at (e.g. the refresh rate of the display it waits for).
+//
+// Time is counted in frames of 1/60 second, the rate of the delay and sound timers. Each
+// frame runs cpu_hz / 60 instructions (or, with COSMAC VIP timing, as many as fit in a VIP
+// frame) and then tick_timers. Leftover time and instructions carry over to later frames, so
+// over time both rates are exact. A speed other than 1 runs the clock faster or slower than
+// real time.
+
+use std::time::Duration;
+
+use crate::{Chip8Error, Emulator};
+
+pub const TIMER_HZ: u32 = 60;
+// Range of speeds the clock runs at. Slower or faster speeds are clamped to it.
+pub const MIN_SPEED: f64 = 0.125;
+pub const MAX_SPEED: f64 = 64.0;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+// Frames run at most for one call to advance. If the frontend stalls (the window is dragged,
+// the machine sleeps), the time past this is dropped instead of racing to catch up.
+const MAX_FRAMES_BEHIND: u64 = 6;
+
+// How many instructions each frame runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    // Instructions per second, spread evenly over the frames.
+    CpuHz(u32),
+    // As many as fit in the machine cycles of a COSMAC VIP frame, see Emulator::run_vip_frame.
+    CosmacVip,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scheduler {
+    timing: Timing,
+    // Emulated seconds per real second.
+    speed: f64,
+    // Time not yet run, in nanoseconds times TIMER_HZ, so a frame is exactly NANOS_PER_SECOND.
+    pending: u64,
+    // Instructions owed to later frames, times TIMER_HZ.
+    instruction_carry: u32,
+}
+
+impl Scheduler {
+    pub fn new(cpu_hz: u32) -> Self {
+        Scheduler::with_timing(Timing::CpuHz(cpu_hz))
+    }
+
+    pub fn with_timing(timing: Timing) -> Self {
+        Scheduler { timing, speed: 1.0, pending: 0, instruction_carry: 0 }
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        self.instruction_carry = 0;
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    // Sets how fast the clock runs: 2.0 for twice real time, 0.5 for half. A speed that isn't
+    // a number runs at real time.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = if speed.is_nan() { 1.0 } else { speed.clamp(MIN_SPEED, MAX_SPEED) };
+    }
+
+    // Adds time that passed on the clock, returning the number of frames now due.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        let elapsed = u64::try_from(elapsed.mul_f64(self.speed).as_nanos()).unwrap_or(u64::MAX);
+        let max_frames = MAX_FRAMES_BEHIND * self.speed.ceil().max(1.0) as u64;
+        let max_pending = (max_frames + 1) * NANOS_PER_SECOND - 1;
+        self.pending = self.pending.saturating_add(elapsed.saturating_mul(TIMER_HZ as u64)).min(max_pending);
+
+        let frames = self.pending / NANOS_PER_SECOND;
+        self.pending %= NANOS_PER_SECOND;
+        frames as u32
+    }
+
+    // Instructions to run in the next frame, before its tick_timers. None with COSMAC VIP
+    // timing, where the instructions themselves decide how many fit.
+    pub fn frame_instructions(&mut self) -> Option<u32> {
+        let Timing::CpuHz(cpu_hz) = self.timing else {
+            return None;
+        };
+        let owed = cpu_hz as u64 + self.instruction_carry as u64;
+        self.instruction_carry = (owed % TIMER_HZ as u64) as u32;
+        Some((owed / TIMER_HZ as u64) as u32)
+    }
+
+    // Runs the instructions of the next frame, before its tick_timers.
+    pub fn run_frame(&mut self, emulator: &mut Emulator) -> Result<(), Chip8Error> {
+        let Some(instructions) = self.frame_instructions() else {
+            return emulator.run_vip_frame();
+        };
+        for _ in 0..instructions {
+            emulator.tick()?;
+        }
+        Ok(())
+    }
+
+    // Real time until the next frame is due.
+    pub fn time_to_next_frame(&self) -> Duration {
+        Duration::from_nanos((NANOS_PER_SECOND - self.pending).div_ceil(TIMER_HZ as u64)).div_f64(self.speed)
+    }
+
+    // Drops the time not yet run, so time spent paused isn't caught up on afterwards, and the
+    // instructions carried over, so the frames that follow run like those of a new Scheduler
+    // (which a movie recorded from here is played back with).
+    pub fn reset(&mut self) {
+        self.pending = 0;
+        self.instruction_carry = 0;
+    }
+}
@@ -0,0 +1,301 @@
+// Save states: a snapshot of the whole machine, serialized to bytes.
+//
+// Layout (multi-byte values are Big Endian, like CHIP-8 itself):
+//   magic    4 bytes  "C8ST"
+//   version  u16
+//   length   u32      length of the payload
+//   payload           machine state, see write_payload
+//   checksum u32      FNV-1a of the payload
+
+use std::fmt;
+
+use crate::{Emulator, KeyWait, Platform, Quirks, AUDIO_PATTERN_SIZE, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, NUM_KEYS, NUM_REGS, STACK_SIZE};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+// Version 2 added the random number generator state, version 3 the COSMAC VIP cycle count,
+// version 4 the keys seen by a waiting FX0A.
+const VERSION: u16 = 4;
+const HEADER_SIZE: usize = 4 + 2 + 4;
+const CHECKSUM_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    // Data does not start with the save state magic header.
+    BadMagic,
+    // Save state was written by a newer (or unknown) version of the format.
+    UnsupportedVersion(u16),
+    // Data ends before the state does.
+    Truncated,
+    // Payload does not match its checksum.
+    ChecksumMismatch,
+    // Payload holds a value no Emulator could be in.
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => write!(f, "unsupported save state version {}", version),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::ChecksumMismatch => write!(f, "save state checksum does not match"),
+            StateError::Corrupt => write!(f, "save state is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+// 32-bit FNV-1a hash.
+pub(crate) fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811C_9DC5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x0100_0193))
+}
+
+pub(crate) struct StateWriter {
+    pub(crate) data: Vec<u8>
+}
+
+impl StateWriter {
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub(crate) fn bytes(&mut self, value: &[u8]) {
+        self.data.extend_from_slice(value);
+    }
+}
+
+pub(crate) struct StateReader<'a> {
+    pub(crate) data: &'a [u8],
+    pub(crate) pos: usize
+}
+
+impl<'a> StateReader<'a> {
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let end = self.pos.checked_add(len).ok_or(StateError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(StateError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Corrupt)
+        }
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+}
+
+fn platform_to_u8(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 0,
+        Platform::SuperChip => 1,
+        Platform::XoChip => 2,
+    }
+}
+
+fn platform_from_u8(value: u8) -> Result<Platform, StateError> {
+    match value {
+        0 => Ok(Platform::Chip8),
+        1 => Ok(Platform::SuperChip),
+        2 => Ok(Platform::XoChip),
+        _ => Err(StateError::Corrupt)
+    }
+}
+
+fn quirks_to_u8(quirks: &Quirks) -> u8 {
+    (quirks.shift_uses_vy as u8)
+        | (quirks.load_store_increments_i as u8) << 1
+        | (quirks.jump_uses_vx as u8) << 2
+        | (quirks.vf_reset as u8) << 3
+        | (quirks.clipping as u8) << 4
+        | (quirks.display_wait as u8) << 5
+        | (quirks.key_wait_on_press as u8) << 6
+        | (quirks.vf_row_count as u8) << 7
+}
+
+fn quirks_from_u8(value: u8) -> Quirks {
+    Quirks {
+        shift_uses_vy: value & 1 != 0,
+        load_store_increments_i: value & (1 << 1) != 0,
+        jump_uses_vx: value & (1 << 2) != 0,
+        vf_reset: value & (1 << 3) != 0,
+        clipping: value & (1 << 4) != 0,
+        display_wait: value & (1 << 5) != 0,
+        key_wait_on_press: value & (1 << 6) != 0,
+        vf_row_count: value & (1 << 7) != 0,
+    }
+}
+
+impl Emulator {
+    // Serializes the full machine state, including platform and quirks.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut payload = StateWriter { data: Vec::new() };
+        self.write_payload(&mut payload);
+
+        let mut state = StateWriter { data: Vec::with_capacity(HEADER_SIZE + payload.data.len() + CHECKSUM_SIZE) };
+        state.bytes(MAGIC);
+        state.u16(VERSION);
+        state.u32(payload.data.len() as u32);
+        state.bytes(&payload.data);
+        state.u32(checksum(&payload.data));
+        state.data
+    }
+
+    // Restores a state produced by save_state.
+    // On error the Emulator is left untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut header = StateReader { data, pos: 0 };
+        if header.bytes(MAGIC.len()).map_err(|_| StateError::BadMagic)? != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = header.u16()?;
+        if version == 0 || version > VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let length = header.u32()? as usize;
+        let payload = header.bytes(length)?;
+        if header.u32()? != checksum(payload) {
+            return Err(StateError::ChecksumMismatch);
+        }
+
+        let mut restored = self.clone();
+        let mut input = StateReader { data: payload, pos: 0 };
+        restored.read_payload(&mut input, version)?;
+        if input.pos != payload.len() {
+            return Err(StateError::Corrupt);
+        }
+        *self = restored;
+        Ok(())
+    }
+
+    fn write_payload(&self, out: &mut StateWriter) {
+        out.u8(platform_to_u8(self.platform));
+        out.u8(quirks_to_u8(&self.quirks));
+        out.u8(self.delay_timer);
+        out.u8(self.sound_timer);
+        out.u32(self.program_counter as u32);
+        out.u8(self.stack_pointer as u8);
+        out.u32(self.ireg as u32);
+        out.bytes(&self.vreg);
+        for value in self.stack {
+            out.u16(value);
+        }
+        for key in self.keys {
+            out.bool(key);
+        }
+        out.u32(self.ram.len() as u32);
+        out.bytes(&self.ram);
+        out.bytes(&self.screen);
+        out.u8(self.selected_planes);
+        out.bytes(&self.audio_pattern);
+        out.u8(self.pitch);
+        out.bool(self.hires);
+        out.bool(self.halted);
+        out.bytes(&self.rpl_flags);
+        out.bool(self.waiting_for_vblank);
+        let rng_state = self.rng.save_state();
+        out.u32(rng_state.len() as u32);
+        out.bytes(&rng_state);
+        out.u32(self.vip_cycles as u32);
+        out.bool(self.key_wait.is_some());
+        let key_wait = self.key_wait.unwrap_or_default();
+        for key in 0..NUM_KEYS {
+            out.bool(key_wait.previous[key]);
+            out.bool(key_wait.pressed[key]);
+        }
+    }
+
+    fn read_payload(&mut self, input: &mut StateReader, version: u16) -> Result<(), StateError> {
+        self.platform = platform_from_u8(input.u8()?)?;
+        self.quirks = quirks_from_u8(input.u8()?);
+        self.delay_timer = input.u8()?;
+        self.sound_timer = input.u8()?;
+        self.program_counter = input.u32()? as usize;
+        self.stack_pointer = input.u8()? as usize;
+        if self.stack_pointer > STACK_SIZE {
+            return Err(StateError::Corrupt);
+        }
+        self.ireg = input.u32()? as usize;
+        self.vreg = input.array::<NUM_REGS>()?;
+        for value in self.stack.iter_mut() {
+            *value = input.u16()?;
+        }
+        for key in 0..NUM_KEYS {
+            self.keys[key] = input.bool()?;
+        }
+        let ram_size = input.u32()? as usize;
+        if ram_size != self.platform.ram_size() {
+            return Err(StateError::Corrupt);
+        }
+        self.ram = input.bytes(ram_size)?.to_vec();
+        self.screen = input.array::<{ HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT }>()?;
+        self.selected_planes = input.u8()?;
+        self.audio_pattern = input.array::<AUDIO_PATTERN_SIZE>()?;
+        self.pitch = input.u8()?;
+        self.hires = input.bool()?;
+        self.halted = input.bool()?;
+        self.rpl_flags = input.array::<NUM_REGS>()?;
+        self.waiting_for_vblank = input.bool()?;
+        // Version 1 states have no RNG state; the current one is kept.
+        if version >= 2 {
+            let length = input.u32()? as usize;
+            if !self.rng.load_state(input.bytes(length)?) {
+                return Err(StateError::Corrupt);
+            }
+        }
+        // Older states start with no cycles owed and no FX0A wait in progress.
+        self.vip_cycles = 0;
+        self.key_wait = None;
+        if version >= 3 {
+            self.vip_cycles = input.u32()? as i32;
+        }
+        if version >= 4 {
+            let waiting = input.bool()?;
+            let mut key_wait = KeyWait::default();
+            for key in 0..NUM_KEYS {
+                key_wait.previous[key] = input.bool()?;
+                key_wait.pressed[key] = input.bool()?;
+            }
+            self.key_wait = Some(key_wait).filter(|_| waiting);
+        }
+        Ok(())
+    }
+}
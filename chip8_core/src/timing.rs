@@ -0,0 +1,112 @@
+// COSMAC VIP timing: instead of a fixed number of instructions per frame, each instruction
+// takes the machine cycles its routine took in the original interpreter, out of the cycles
+// the VIP's CPU had per 60 Hz frame.
+//
+// Exact figures, from the hardware: the RCA 1802 runs at 1.7609 MHz with 8 clocks per
+// machine cycle, giving 3668 machine cycles per frame, and the CDP1861 video chip takes one
+// of them for each byte it shows, 1024 for 128 scan lines of 8 bytes.
+//
+// Approximate figures: the cycles of the interrupt routine (which also counts down the
+// timers) and of each instruction. They are counted from the structure of the interpreter's
+// routines (its loops, and the work done per byte, bit or digit) rather than taken from a
+// cycle-exact trace of the ROM, which also varies with the operands in ways not modelled
+// here. Timing is therefore faithful in proportion (a sprite takes far longer than a jump)
+// rather than to the cycle.
+//
+// As on the VIP, DXYN waits for the vertical blank before drawing: the rest of the frame is
+// idle and the drawing takes cycles from the next one. Cycles an instruction overruns the
+// frame by are likewise taken from the next frame.
+
+use crate::{Chip8Error, Emulator, Instruction};
+
+// Machine cycles per frame: 1.7609 MHz / 8 clocks per cycle / 60 Hz (exact).
+pub const VIP_CYCLES_PER_FRAME: u32 = 3668;
+// Cycles the CPU loses each frame to video DMA (exact).
+const VIDEO_DMA_CYCLES: u32 = 128 * 8;
+// Cycles of the display interrupt routine, including the timers (approximate).
+const INTERRUPT_CYCLES: u32 = 46;
+// Cycles left for the interpreter each frame.
+const FRAME_BUDGET: i32 = (VIP_CYCLES_PER_FRAME - VIDEO_DMA_CYCLES - INTERRUPT_CYCLES) as i32;
+
+// Cycles every instruction spends being fetched and dispatched by the interpreter loop.
+// This and the costs below are approximate, see above.
+const FETCH_CYCLES: u32 = 40;
+// Extra cycles of a skip instruction that skips.
+const SKIP_CYCLES: u32 = 4;
+
+impl Emulator {
+    // Runs the instructions of one frame under COSMAC VIP timing, to be followed by tick_timers.
+    // Stops early when the program draws, halts or hits a breakpoint.
+    pub fn run_vip_frame(&mut self) -> Result<(), Chip8Error> {
+        self.vip_cycles += FRAME_BUDGET;
+        while self.vip_cycles > 0 && !self.halted && !self.waiting_for_vblank && !self.is_paused() {
+            // Opcodes that don't decode are left to tick to report.
+            let Some(instruction) = self.peek_opcode().and_then(Instruction::decode) else {
+                return self.tick();
+            };
+            let cycles = self.instruction_cycles(instruction) as i32;
+            let pc = self.program_counter;
+            self.tick()?;
+            if self.program_counter == pc && self.is_paused() {
+                // Stopped at a breakpoint without executing.
+                break;
+            }
+
+            if let Instruction::Drw { .. } = instruction {
+                self.vip_cycles = -cycles;
+                self.waiting_for_vblank = true;
+            } else {
+                self.vip_cycles -= cycles;
+            }
+        }
+        // Cycles left over are spent idle until the vertical blank.
+        self.vip_cycles = self.vip_cycles.min(0);
+        Ok(())
+    }
+
+    // Machine cycles the VIP interpreter takes to run an instruction, given the current
+    // registers and keys. Instructions the VIP didn't have cost only their fetch.
+    fn instruction_cycles(&self, instruction: Instruction) -> u32 {
+        let skip = |skips: bool| if skips { 10 + SKIP_CYCLES } else { 10 };
+        let vx = |x: u8| self.vreg[x as usize];
+
+        FETCH_CYCLES + match instruction {
+            // 256 bytes of display memory cleared in a loop of 6 cycles per byte.
+            Instruction::Cls => 24 + 256 * 6,
+            Instruction::Ret => 10,
+            Instruction::Jp(_) => 12,
+            Instruction::Call(_) => 26,
+            Instruction::SeVxKk { x, kk } => skip(vx(x) == kk),
+            Instruction::SneVxKk { x, kk } => skip(vx(x) != kk),
+            Instruction::SeVxVy { x, y } => skip(vx(x) == vx(y)),
+            Instruction::SneVxVy { x, y } => skip(vx(x) != vx(y)),
+            Instruction::LdVxKk { .. } => 6,
+            Instruction::AddVxKk { .. } => 10,
+            // 8XYN builds and calls a small routine in RAM for its ALU operation.
+            Instruction::LdVxVy { .. } | Instruction::OrVxVy { .. } | Instruction::AndVxVy { .. }
+            | Instruction::XorVxVy { .. } | Instruction::AddVxVy { .. } | Instruction::SubVxVy { .. }
+            | Instruction::ShrVxVy { .. } | Instruction::SubnVxVy { .. } | Instruction::ShlVxVy { .. } => 44,
+            Instruction::LdI(_) => 8,
+            Instruction::JpV0(_) => 22,
+            Instruction::RndVxKk { .. } => 36,
+            // Each row of the sprite is shifted into place one bit at a time, then XORed
+            // into the two display bytes it covers.
+            Instruction::Drw { x, n, .. } => 26 + n as u32 * (46 + 8 * (vx(x) % 8) as u32),
+            Instruction::SkpVx { x } => skip(self.is_key_pressed(vx(x) as usize)),
+            Instruction::SknpVx { x } => skip(!self.is_key_pressed(vx(x) as usize)),
+            Instruction::LdVxDt { .. } => 6,
+            // Each time the key is polled.
+            Instruction::LdVxK { .. } => 16,
+            Instruction::LdDtVx { .. } | Instruction::LdStVx { .. } => 10,
+            Instruction::AddIVx { .. } => 12,
+            Instruction::LdFVx { .. } => 10,
+            // Digits are found by repeated subtraction, 8 cycles each.
+            Instruction::LdBVx { x } => {
+                let value = vx(x) as u32;
+                20 + 8 * (value / 100 + value / 10 % 10 + value % 10)
+            },
+            Instruction::LdIVx { x } | Instruction::LdVxI { x } => 14 + 14 * (x as u32 + 1),
+            _ => 0
+        }
+    }
+}
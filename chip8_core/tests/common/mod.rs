@@ -0,0 +1,49 @@
+// Fixtures shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+
+use chip8_core::{Emulator, Platform, Quirks, Scheduler};
+
+// Instructions per frame the golden images and movies were made with.
+pub const TICKS_PER_FRAME: u32 = 8;
+
+// Path of a file in the repository, e.g. `games/roms/bug_game.ch8`.
+pub fn repo_path(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..").join(path)
+}
+
+// Opcodes as the big endian bytes of a ROM.
+pub fn program(opcodes: &[u16]) -> Vec<u8> {
+    opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect()
+}
+
+// A CHIP-8 Emulator with the given quirks and program loaded.
+pub fn load(quirks: Quirks, opcodes: &[u16]) -> Emulator {
+    load_on(Platform::Chip8, quirks, opcodes)
+}
+
+pub fn load_on(platform: Platform, quirks: Quirks, opcodes: &[u16]) -> Emulator {
+    let mut chip8 = Emulator::with_platform(platform, quirks);
+    chip8.load_data(&program(opcodes)).unwrap();
+    chip8
+}
+
+// A seeded Emulator with a ROM file of the repository loaded.
+pub fn load_rom(path: &str, seed: u64) -> Emulator {
+    let mut chip8 = Emulator::with_seed(seed);
+    chip8.load_data(&fs::read(repo_path(path)).unwrap()).unwrap();
+    chip8
+}
+
+pub fn tick(chip8: &mut Emulator, instructions: usize) {
+    for _ in 0..instructions {
+        chip8.tick().unwrap();
+    }
+}
+
+// Runs a frame's instructions and ends the frame.
+pub fn run_frame(chip8: &mut Emulator, scheduler: &mut Scheduler) {
+    scheduler.run_frame(chip8).unwrap();
+    chip8.tick_timers();
+}
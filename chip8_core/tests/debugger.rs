@@ -0,0 +1,158 @@
+mod common;
+
+use chip8_core::{format_registers, parse_address, Emulator, MemoryAccess, Platform, Quirks, StopReason, WatchHit, WatchKind, Watchpoint};
+use common::{load, load_on, tick};
+
+// Ticks until the Emulator pauses, at most `limit` times.
+fn run_until_paused(chip8: &mut Emulator, limit: usize) {
+    for _ in 0..limit {
+        if chip8.is_paused() {
+            return;
+        }
+        chip8.tick().unwrap();
+    }
+    assert!(chip8.is_paused(), "still running after {} ticks", limit);
+}
+
+#[test]
+fn step_over_runs_a_call_to_its_return_address() {
+    // 200: CALL 206; 202: ADD V0, 1; 204: JP 204
+    // 206: CALL 20C; 208: ADD V1, 1; 20A: RET; 20C: ADD V2, 1; 20E: RET
+    let mut chip8 = load(Quirks::default(), &[0x2206, 0x7001, 0x1204, 0x220C, 0x7101, 0x00EE, 0x7201, 0x00EE]);
+    chip8.pause();
+    chip8.step_over().unwrap();
+    run_until_paused(&mut chip8, 10);
+
+    let registers = chip8.registers();
+    assert_eq!(chip8.stop_reason(), Some(StopReason::StepComplete));
+    assert_eq!(registers.pc, 0x202);
+    assert_eq!(registers.sp, 0);
+    // The nested call ran, the instruction after the call didn't.
+    assert_eq!(registers.v[..3], [0, 1, 1]);
+}
+
+#[test]
+fn step_over_stops_at_breakpoints_in_the_subroutine() {
+    // 200: CALL 204; 202: JP 202; 204: ADD V1, 1; 206: RET
+    let mut chip8 = load(Quirks::default(), &[0x2204, 0x1202, 0x7101, 0x00EE]);
+    chip8.add_breakpoint(0x206);
+    chip8.pause();
+    chip8.step_over().unwrap();
+    run_until_paused(&mut chip8, 10);
+    assert_eq!(chip8.stop_reason(), Some(StopReason::Breakpoint { addr: 0x206 }));
+}
+
+#[test]
+fn breakpoint_does_not_fire_again_on_resume() {
+    // 200: ADD V0, 1; 202: JP 200
+    let mut chip8 = load(Quirks::default(), &[0x7001, 0x1200]);
+    chip8.add_breakpoint(0x200);
+    chip8.tick().unwrap();
+    assert_eq!(chip8.stop_reason(), Some(StopReason::Breakpoint { addr: 0x200 }));
+    assert_eq!(chip8.registers().v[0], 0);
+
+    // The instruction under the breakpoint runs, and the breakpoint fires the next time round.
+    chip8.resume();
+    tick(&mut chip8, 3);
+    assert_eq!(chip8.stop_reason(), Some(StopReason::Breakpoint { addr: 0x200 }));
+    assert_eq!(chip8.registers().v[0], 1);
+    assert_eq!(chip8.registers().pc, 0x200);
+
+    // Stepping from a breakpoint runs the instruction under it.
+    chip8.step().unwrap();
+    assert_eq!(chip8.stop_reason(), Some(StopReason::StepComplete));
+    assert_eq!(chip8.registers().v[0], 2);
+}
+
+#[test]
+fn step_reports_when_nothing_can_run() {
+    // DRW V0, V0, 1; ADD V0, 1: the draw waits for the vertical blank.
+    let mut chip8 = load(Quirks { display_wait: true, ..Quirks::default() }, &[0xD001, 0x7001]);
+    chip8.pause();
+    chip8.step().unwrap();
+    assert_eq!(chip8.stop_reason(), Some(StopReason::StepComplete));
+    chip8.step().unwrap();
+    assert_eq!(chip8.stop_reason(), Some(StopReason::Blocked));
+    assert_eq!((chip8.registers().pc, chip8.registers().v[0]), (0x202, 0));
+
+    // The wait ends with the frame.
+    chip8.tick_timers();
+    chip8.step().unwrap();
+    assert_eq!(chip8.stop_reason(), Some(StopReason::StepComplete));
+    assert_eq!(chip8.registers().v[0], 1);
+
+    // EXIT
+    let mut chip8 = load_on(Platform::SuperChip, Quirks::SUPER_CHIP, &[0x00FD, 0x7001]);
+    chip8.pause();
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    assert_eq!(chip8.stop_reason(), Some(StopReason::Blocked));
+    assert_eq!(chip8.registers().v[0], 0);
+}
+
+#[test]
+fn write_watchpoint_pauses_after_fx55() {
+    // LD I, 300; LD V0, 5; LD V1, 7; LD [I], V1; JP 208
+    let mut chip8 = load(Quirks::default(), &[0xA300, 0x6005, 0x6107, 0xF155, 0x1208]);
+    chip8.add_watchpoint(Watchpoint { addresses: 0x301..=0x301, kind: WatchKind::Write, pause: true });
+    run_until_paused(&mut chip8, 10);
+
+    let hit = WatchHit { addr: 0x301, access: MemoryAccess::Write, pc: 0x206, opcode: 0xF155, old_value: 0, new_value: 7 };
+    assert_eq!(chip8.stop_reason(), Some(StopReason::Watchpoint(hit)));
+    // The instruction has completed.
+    assert_eq!(chip8.registers().pc, 0x208);
+    assert_eq!(chip8.take_watch_hits(), [hit]);
+}
+
+#[test]
+fn write_watchpoint_logs_each_fx33_digit() {
+    // LD V0, 123; LD I, 300; LD B, V0
+    let mut chip8 = load(Quirks::default(), &[0x607B, 0xA300, 0xF033]);
+    chip8.add_watchpoint(Watchpoint { addresses: 0x300..=0x302, kind: WatchKind::Write, pause: false });
+    // Fetching the program reads it, which a write watchpoint ignores.
+    chip8.add_watchpoint(Watchpoint { addresses: 0x200..=0x2FF, kind: WatchKind::Write, pause: true });
+    tick(&mut chip8, 3);
+
+    assert!(!chip8.is_paused());
+    let hits = chip8.take_watch_hits();
+    let digits: Vec<_> = hits.iter().map(|hit| (hit.addr, hit.new_value)).collect();
+    assert_eq!(digits, [(0x300, 1), (0x301, 2), (0x302, 3)]);
+    assert!(hits.iter().all(|hit| hit.access == MemoryAccess::Write && hit.pc == 0x204 && hit.opcode == 0xF033));
+    assert!(chip8.take_watch_hits().is_empty());
+}
+
+#[test]
+fn read_watchpoint_ignores_writes() {
+    // LD I, 300; LD [I], V0; LD V0, [I]
+    let mut chip8 = load(Quirks::default(), &[0xA300, 0xF055, 0xF065]);
+    chip8.add_watchpoint(Watchpoint { addresses: 0x300..=0x300, kind: WatchKind::Read, pause: false });
+    tick(&mut chip8, 2);
+    assert!(chip8.take_watch_hits().is_empty());
+
+    tick(&mut chip8, 1);
+    let hits = chip8.take_watch_hits();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].access, MemoryAccess::Read);
+}
+
+#[test]
+fn addresses_parse_with_or_without_the_prefix() {
+    assert_eq!(parse_address("0x2A4"), Some(0x2A4));
+    assert_eq!(parse_address("0X2a4"), Some(0x2A4));
+    assert_eq!(parse_address("2A4"), Some(0x2A4));
+    assert_eq!(parse_address("0x"), None);
+    assert_eq!(parse_address("2G4"), None);
+}
+
+#[test]
+fn registers_are_formatted_for_the_frontends() {
+    // CALL 204; JP 202; LD VA, 5B; LD I, 2F0
+    let mut chip8 = load(Quirks::default(), &[0x2204, 0x1202, 0x6A5B, 0xA2F0]);
+    tick(&mut chip8, 2);
+    assert_eq!(format_registers(&chip8), "\
+PC 0x206 [A2F0]  I 0x000  DT   0  ST   0
+V0 00  V1 00  V2 00  V3 00  V4 00  V5 00  V6 00  V7 00
+V8 00  V9 00  VA 5B  VB 00  VC 00  VD 00  VE 00  VF 00
+Stack [0x202]
+");
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use chip8_core::{disassemble, format_listing, Syntax};
+use common::program;
+
+// Opcode, Cowgod syntax, Octo syntax.
+const INSTRUCTIONS: [(u16, &str, &str); 36] = [
+    (0x00E0, "CLS", "clear"),
+    (0x00EE, "RET", "return"),
+    (0x00C4, "SCD 4", "scroll-down 4"),
+    (0x00D2, "SCU 2", "scroll-up 2"),
+    (0x00FB, "SCR", "scroll-right"),
+    (0x00FF, "HIGH", "hires"),
+    (0x1234, "JP 0x234", "jump 0x234"),
+    (0x2ABC, "CALL 0xABC", ":call 0xABC"),
+    (0x3A0F, "SE VA, 0x0F", "if va != 0x0F then"),
+    (0x4B10, "SNE VB, 0x10", "if vb == 0x10 then"),
+    (0x5120, "SE V1, V2", "if v1 != v2 then"),
+    (0x5312, "LD [I], V3-V1", "save v3 - v1"),
+    (0x5133, "LD V1-V3, [I]", "load v1 - v3"),
+    (0x6CFF, "LD VC, 0xFF", "vc := 0xFF"),
+    (0x7D01, "ADD VD, 0x01", "vd += 0x01"),
+    (0x8120, "LD V1, V2", "v1 := v2"),
+    (0x8124, "ADD V1, V2", "v1 += v2"),
+    (0x8127, "SUBN V1, V2", "v1 =- v2"),
+    (0x812E, "SHL V1, V2", "v1 <<= v2"),
+    (0x9120, "SNE V1, V2", "if v1 == v2 then"),
+    (0xA2F0, "LD I, 0x2F0", "i := 0x2F0"),
+    (0xB300, "JP V0, 0x300", "jump0 0x300"),
+    (0xC07F, "RND V0, 0x7F", "v0 := random 0x7F"),
+    (0xD125, "DRW V1, V2, 5", "sprite v1 v2 5"),
+    (0xE19E, "SKP V1", "if v1 -key then"),
+    (0xE1A1, "SKNP V1", "if v1 key then"),
+    (0xF201, "PLANE 2", "plane 2"),
+    (0xF002, "AUDIO", "audio"),
+    (0xF30A, "LD V3, K", "v3 := key"),
+    (0xF318, "LD ST, V3", "buzzer := v3"),
+    (0xF329, "LD F, V3", "i := hex v3"),
+    (0xF330, "LD HF, V3", "i := bighex v3"),
+    (0xF333, "LD B, V3", "bcd v3"),
+    (0xF33A, "PITCH V3", "pitch := v3"),
+    (0xF365, "LD V3, [I]", "load v3"),
+    (0xF385, "LD V3, R", "loadflags v3"),
+];
+
+// Words that are not instructions, and are listed as data.
+const DATA: [(u16, &str, &str); 5] = [
+    // SYS calls are machine code for the host CPU.
+    (0x0123, "DB 0x01, 0x23", "0x01 0x23"),
+    (0x5121, "DB 0x51, 0x21", "0x51 0x21"),
+    (0x812F, "DB 0x81, 0x2F", "0x81 0x2F"),
+    (0xE100, "DB 0xE1, 0x00", "0xE1 0x00"),
+    (0xFFFF, "DB 0xFF, 0xFF", "0xFF 0xFF"),
+];
+
+#[test]
+fn instructions_in_both_syntaxes() {
+    for (opcode, cowgod, octo) in INSTRUCTIONS {
+        let listing = disassemble(&opcode.to_be_bytes(), 0x200);
+        assert_eq!(listing.len(), 1, "{:04X}", opcode);
+        let instruction = &listing[0];
+        assert!(instruction.is_instruction, "{:04X}", opcode);
+        assert_eq!(instruction.cowgod, cowgod);
+        assert_eq!(instruction.octo, octo);
+    }
+}
+
+#[test]
+fn unknown_words_are_data() {
+    for (word, cowgod, octo) in DATA {
+        let listing = disassemble(&word.to_be_bytes(), 0x200);
+        assert_eq!(listing.len(), 1, "{:04X}", word);
+        let data = &listing[0];
+        assert!(!data.is_instruction, "{:04X}", word);
+        assert_eq!(data.target, None);
+        assert_eq!(data.cowgod, cowgod);
+        assert_eq!(data.octo, octo);
+    }
+}
+
+#[test]
+fn long_load_takes_four_bytes() {
+    // LD I, long 1234; CLS
+    let listing = disassemble(&program(&[0xF000, 0x1234, 0x00E0]), 0x200);
+    assert_eq!(listing.len(), 2);
+    assert_eq!(listing[0].bytes, [0xF0, 0x00, 0x12, 0x34]);
+    assert_eq!(listing[0].cowgod, "LD I, long 0x1234");
+    assert_eq!(listing[0].octo, "i := long 0x1234");
+    assert_eq!(listing[1].addr, 0x204);
+
+    // Without the address word it can only be data.
+    let listing = disassemble(&program(&[0xF000]), 0x200);
+    assert!(!listing[0].is_instruction);
+}
+
+#[test]
+fn trailing_odd_byte_is_data() {
+    let listing = disassemble(&[0x00, 0xE0, 0xAB], 0x200);
+    assert_eq!(listing.len(), 2);
+    assert_eq!((listing[1].addr, listing[1].bytes.as_slice()), (0x202, [0xAB].as_slice()));
+    assert_eq!(listing[1].cowgod, "DB 0xAB");
+    assert_eq!(listing[1].octo, "0xAB");
+}
+
+#[test]
+fn listings_label_targets_inside_the_program() {
+    // 200: CALL 204; 202: JP 300; 204: RET
+    let listing = disassemble(&program(&[0x2204, 0x1300, 0x00EE]), 0x200);
+    assert_eq!(listing[0].target, Some(0x204));
+
+    assert_eq!(format_listing(&listing, Syntax::Cowgod), "\
+0x200  2204      CALL label_204
+0x202  1300      JP 0x300
+label_204:
+0x204  00EE      RET
+");
+    assert_eq!(format_listing(&listing, Syntax::Octo), "\
+\t:call label_204         # 0x200 2204
+\tjump 0x300              # 0x202 1300
+: label_204
+\treturn                  # 0x204 00EE
+");
+}
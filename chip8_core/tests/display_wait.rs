@@ -0,0 +1,40 @@
+mod common;
+
+use chip8_core::Quirks;
+use common::load;
+
+// LD I, 0; DRW V0, V0, 5; ADD V1, 1; JP 202
+const DRAW_LOOP: [u16; 4] = [0xA000, 0xD005, 0x7101, 0x1202];
+
+// Draws counted over `frames` frames of 20 instructions.
+fn draws(quirks: Quirks, frames: usize) -> usize {
+    let mut chip8 = load(quirks, &DRAW_LOOP);
+    let mut draws = 0;
+    for _ in 0..frames {
+        for _ in 0..20 {
+            if chip8.peek_opcode() == Some(0xD005) {
+                draws += 1;
+            }
+            chip8.tick().unwrap();
+        }
+        chip8.tick_timers();
+    }
+    draws
+}
+
+#[test]
+fn draws_once_per_frame_with_display_wait() {
+    assert_eq!(draws(Quirks { display_wait: true, ..Quirks::default() }, 10), 10);
+    assert!(draws(Quirks::default(), 10) > 10);
+}
+
+#[test]
+fn stalls_until_the_next_vertical_blank() {
+    let mut chip8 = load(Quirks { display_wait: true, ..Quirks::default() }, &DRAW_LOOP);
+    common::tick(&mut chip8, 5);
+    assert_eq!(chip8.registers().pc, 0x204);
+
+    chip8.tick_timers();
+    chip8.tick().unwrap();
+    assert_eq!(chip8.registers().pc, 0x206);
+}
@@ -0,0 +1,77 @@
+// Golden framebuffer tests: runs the bundled ROMs with scripted input and compares the final
+// screen against the text images in tests/golden.
+//
+// Run with UPDATE_GOLDENS=1 to rewrite the images from the current output.
+
+mod common;
+
+use std::{env, fs, path::PathBuf};
+
+use chip8_core::{run_frames, KeyPress, RunOutcome, Scheduler, TIMER_HZ};
+use common::{load_rom, TICKS_PER_FRAME};
+
+const SEED: u64 = 0xC8;
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.txt", name))
+}
+
+// Runs the ROM for `frames` frames, like the headless frontends do, and returns the final screen.
+fn run(rom: &str, frames: usize, keys: &[KeyPress]) -> String {
+    let mut chip8 = load_rom(rom, SEED);
+    let mut scheduler = Scheduler::new(TICKS_PER_FRAME * TIMER_HZ);
+    let (outcome, _) = run_frames(&mut chip8, &mut scheduler, frames, keys, None, |_| ());
+    assert_eq!(outcome, RunOutcome::FramesElapsed);
+
+    chip8.get_display().to_string()
+}
+
+fn check_golden(name: &str, screen: &str) {
+    let path = golden_path(name);
+    if env::var_os("UPDATE_GOLDENS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, screen).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Unable to read {}: {} (run with UPDATE_GOLDENS=1 to create it)", path.display(), err));
+    assert!(screen == expected, "{} does not match {}\n\nexpected:\n{}\nactual:\n{}", name, path.display(), expected, screen);
+}
+
+#[test]
+fn walk_wait_input() {
+    // Right, right, down, left: FX0A waits for each tap.
+    let keys = [
+        KeyPress { frame: 10, key: 9, hold: 1 },
+        KeyPress { frame: 30, key: 9, hold: 1 },
+        KeyPress { frame: 50, key: 8, hold: 1 },
+        KeyPress { frame: 70, key: 7, hold: 1 },
+    ];
+    check_golden("walk_wait_input", &run("tests/roms/walk_wait_input.ch8", 100, &keys));
+}
+
+#[test]
+fn walk_passive_input() {
+    // Held keys move the player every frame the ROM polls them.
+    let keys = [
+        KeyPress { frame: 10, key: 9, hold: 20 },
+        KeyPress { frame: 40, key: 8, hold: 10 },
+    ];
+    check_golden("walk_passive_input", &run("tests/roms/walk_passive_input.ch8", 80, &keys));
+}
+
+#[test]
+fn random_position() {
+    check_golden("random_position", &run("tests/roms/random_position.ch8", 200, &[]));
+}
+
+#[test]
+fn bug_game() {
+    let keys = [
+        KeyPress { frame: 60, key: 9, hold: 30 },
+        KeyPress { frame: 120, key: 5, hold: 20 },
+        KeyPress { frame: 160, key: 7, hold: 15 },
+    ];
+    check_golden("bug_game", &run("games/roms/bug_game.ch8", 240, &keys));
+}
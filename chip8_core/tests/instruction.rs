@@ -0,0 +1,34 @@
+mod common;
+
+use chip8_core::{Instruction, Quirks};
+use common::{load, tick};
+
+#[test]
+fn decode_encode_round_trips_every_opcode() {
+    for opcode in 0..=u16::MAX {
+        if let Some(instruction) = Instruction::decode(opcode) {
+            assert_eq!(instruction.encode(), opcode, "{:?}", instruction);
+        }
+    }
+}
+
+#[test]
+fn unknown_opcodes_do_not_decode() {
+    for opcode in [0x5001, 0x800F, 0x9001, 0xE000, 0xF0FF, 0xF102] {
+        assert_eq!(Instruction::decode(opcode), None, "{:04X}", opcode);
+    }
+}
+
+#[test]
+fn subn_sets_vx_to_vy_minus_vx() {
+    // LD V0, 5; LD V1, 8; SUBN V0, V1; LD V2, 8; LD V3, 5; SUBN V2, V3
+    let mut chip8 = load(Quirks::default(), &[0x6005, 0x6108, 0x8017, 0x6208, 0x6305, 0x8237]);
+    tick(&mut chip8, 3);
+    let regs = chip8.registers();
+    assert_eq!((regs.v[0], regs.v[1], regs.v[0xF]), (3, 8, 1));
+
+    // Vy < Vx borrows.
+    tick(&mut chip8, 3);
+    let regs = chip8.registers();
+    assert_eq!((regs.v[2], regs.v[3], regs.v[0xF]), (0xFD, 5, 0));
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use chip8_core::{Emulator, Quirks};
+
+// LD V0, K; JP 202
+const WAIT_FOR_KEY: [u16; 2] = [0xF00A, 0x1202];
+
+fn load(quirks: Quirks) -> Emulator {
+    common::load(quirks, &WAIT_FOR_KEY)
+}
+
+fn is_waiting(chip8: &Emulator) -> bool {
+    chip8.registers().pc == 0x200
+}
+
+#[test]
+fn waits_for_a_press_and_release() {
+    let mut chip8 = load(Quirks::default());
+    chip8.tick().unwrap();
+    chip8.keypress(5, true).unwrap();
+    chip8.tick().unwrap();
+    assert!(is_waiting(&chip8));
+
+    chip8.keypress(5, false).unwrap();
+    chip8.tick().unwrap();
+    assert!(!is_waiting(&chip8));
+    assert_eq!(chip8.registers().v[0], 5);
+}
+
+#[test]
+fn keys_held_before_the_wait_must_be_pressed_again() {
+    let mut chip8 = load(Quirks::default());
+    chip8.keypress(3, true).unwrap();
+    chip8.tick().unwrap();
+    chip8.keypress(3, false).unwrap();
+    chip8.tick().unwrap();
+    assert!(is_waiting(&chip8));
+
+    // The wait survives a save state.
+    chip8.keypress(3, true).unwrap();
+    chip8.tick().unwrap();
+    let mut restored = load(Quirks::default());
+    restored.load_state(&chip8.save_state()).unwrap();
+    restored.keypress(3, false).unwrap();
+    restored.tick().unwrap();
+    assert!(!is_waiting(&restored));
+    assert_eq!(restored.registers().v[0], 3);
+}
+
+#[test]
+fn key_wait_on_press_quirk_takes_a_held_key() {
+    let mut chip8 = load(Quirks { key_wait_on_press: true, ..Quirks::default() });
+    chip8.keypress(7, true).unwrap();
+    chip8.tick().unwrap();
+    assert!(!is_waiting(&chip8));
+    assert_eq!(chip8.registers().v[0], 7);
+}
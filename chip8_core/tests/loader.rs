@@ -0,0 +1,51 @@
+mod common;
+
+use chip8_core::{load_rom, platform_for_rom, Platform, Quirks, RomSettings};
+use common::program;
+
+#[test]
+fn platform_comes_from_the_extension() {
+    assert_eq!(platform_for_rom("roms/game.sc8"), Platform::SuperChip);
+    assert_eq!(platform_for_rom("roms/game.xo8"), Platform::XoChip);
+    assert_eq!(platform_for_rom("games/roms/bug_game.ch8"), Platform::Chip8);
+    assert_eq!(platform_for_rom("roms/game"), Platform::Chip8);
+}
+
+#[test]
+fn settings_override_the_rom() {
+    let rom = program(&[0x00E0]);
+
+    let chip8 = load_rom("game.sc8", &rom, &RomSettings::default()).unwrap();
+    assert_eq!((chip8.platform(), chip8.quirks()), (Platform::SuperChip, Quirks::SUPER_CHIP));
+    let chip8 = load_rom("game.ch8", &rom, &RomSettings::default()).unwrap();
+    assert_eq!((chip8.platform(), chip8.quirks()), (Platform::Chip8, Quirks::default()));
+
+    let settings = RomSettings { platform: Some(Platform::XoChip), ..RomSettings::default() };
+    let chip8 = load_rom("game.sc8", &rom, &settings).unwrap();
+    assert_eq!((chip8.platform(), chip8.quirks()), (Platform::XoChip, Quirks::XO_CHIP));
+
+    let settings = RomSettings { quirks: Some(Quirks::COSMAC_VIP), display_wait: Some(false), key_wait_on_press: true, ..RomSettings::default() };
+    let chip8 = load_rom("game.xo8", &rom, &settings).unwrap();
+    assert_eq!(chip8.platform(), Platform::XoChip);
+    assert_eq!(chip8.quirks(), Quirks { display_wait: false, key_wait_on_press: true, ..Quirks::COSMAC_VIP });
+    assert_eq!(chip8.memory()[0x200..0x202], [0x00, 0xE0]);
+}
+
+#[test]
+fn seeded_roms_draw_the_same_numbers() {
+    // RND V0, FF
+    let rom = program(&[0xC0FF]);
+    let settings = RomSettings { seed: Some(7), ..RomSettings::default() };
+    let mut first = load_rom("game.ch8", &rom, &settings).unwrap();
+    let mut second = load_rom("game.ch8", &rom, &settings).unwrap();
+    first.tick().unwrap();
+    second.tick().unwrap();
+    assert_eq!(first.registers().v[0], second.registers().v[0]);
+}
+
+#[test]
+fn roms_too_big_for_the_platform_are_refused() {
+    let rom = vec![0; 0x1000];
+    assert!(load_rom("game.ch8", &rom, &RomSettings::default()).is_err());
+    assert!(load_rom("game.xo8", &rom, &RomSettings::default()).is_ok());
+}
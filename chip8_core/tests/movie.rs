@@ -0,0 +1,75 @@
+mod common;
+
+use chip8_core::{Emulator, Movie, MovieError, MoviePlayer, Quirks, Scheduler, Timing};
+use common::{load, load_rom, run_frame};
+
+fn record_bug_game(timing: Timing) -> (Movie, Emulator) {
+    let mut chip8 = load_rom("games/roms/bug_game.ch8", 1);
+    let mut scheduler = Scheduler::with_timing(timing);
+    for _ in 0..30 {
+        run_frame(&mut chip8, &mut scheduler);
+    }
+
+    chip8.start_recording(&mut scheduler);
+    for frame in 0..200 {
+        match frame {
+            20 => chip8.keypress(9, true).unwrap(),
+            60 => chip8.keypress(9, false).unwrap(),
+            80 => chip8.keypress(8, true).unwrap(),
+            110 => chip8.keypress(8, false).unwrap(),
+            _ => ()
+        }
+        run_frame(&mut chip8, &mut scheduler);
+    }
+    (chip8.stop_recording().unwrap(), chip8)
+}
+
+#[test]
+fn playback_reproduces_recording() {
+    // 500 Hz is not a whole number of instructions per frame.
+    for timing in [Timing::CpuHz(500), Timing::CosmacVip] {
+        let (movie, recorded) = record_bug_game(timing);
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        assert_eq!(movie.frames(), 200);
+        assert_eq!(movie.events().len(), 4);
+        assert_eq!(movie.timing(), timing);
+
+        let mut chip8 = Emulator::with_seed(2);
+        let mut scheduler = movie.scheduler();
+        let mut player = MoviePlayer::start(movie, &mut chip8).unwrap();
+        while !player.is_finished() {
+            player.play_frame(&mut chip8).unwrap();
+            run_frame(&mut chip8, &mut scheduler);
+        }
+
+        assert!(player.verify(&chip8));
+        assert_eq!(chip8.save_state(), recorded.save_state());
+    }
+}
+
+#[test]
+fn screen_hash_is_taken_at_the_end_of_the_last_frame() {
+    // LD I, 0; DRW V0, V0, 5; JP 202: the digit flips on every other instruction.
+    let mut chip8 = load(Quirks::default(), &[0xA000, 0xD005, 0x1202]);
+    let mut scheduler = Scheduler::new(180);
+    chip8.start_recording(&mut scheduler);
+    run_frame(&mut chip8, &mut scheduler);
+    let frame_end_hash = chip8.screen_hash();
+
+    // Instructions of a frame that never ended.
+    common::tick(&mut chip8, 2);
+    let movie = chip8.stop_recording().unwrap();
+    assert_ne!(chip8.screen_hash(), frame_end_hash);
+    assert_eq!(movie.screen_hash(), frame_end_hash);
+}
+
+#[test]
+fn damaged_movie_is_rejected() {
+    let (movie, _) = record_bug_game(Timing::CpuHz(480));
+    let mut data = movie.to_bytes();
+    let middle = data.len() / 2;
+    data[middle] ^= 0xFF;
+
+    assert_eq!(Movie::from_bytes(&data), Err(MovieError::ChecksumMismatch));
+    assert_eq!(Movie::from_bytes(&data[..middle]), Err(MovieError::Truncated));
+}
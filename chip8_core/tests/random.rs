@@ -0,0 +1,40 @@
+mod common;
+
+use chip8_core::Emulator;
+
+// Fills V0-VF with CXFF (RND Vx, 0xFF) results and loops.
+const RANDOM_ROM: [u8; 34] = [
+    0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF, 0xC3, 0xFF, 0xC4, 0xFF, 0xC5, 0xFF, 0xC6, 0xFF, 0xC7, 0xFF,
+    0xC8, 0xFF, 0xC9, 0xFF, 0xCA, 0xFF, 0xCB, 0xFF, 0xCC, 0xFF, 0xCD, 0xFF, 0xCE, 0xFF, 0xCF, 0xFF,
+    0x12, 0x00,
+];
+
+fn run(chip8: &mut Emulator) -> [u8; 16] {
+    common::tick(chip8, RANDOM_ROM.len() / 2);
+    chip8.registers().v
+}
+
+#[test]
+fn same_seed_gives_same_numbers() {
+    let mut a = Emulator::with_seed(42);
+    let mut b = Emulator::with_seed(42);
+    a.load_data(&RANDOM_ROM).unwrap();
+    b.load_data(&RANDOM_ROM).unwrap();
+
+    let first = run(&mut a);
+    assert_eq!(first, run(&mut b));
+    assert_ne!(first, run(&mut a));
+}
+
+#[test]
+fn save_state_restores_random_numbers() {
+    let mut chip8 = Emulator::with_seed(7);
+    chip8.load_data(&RANDOM_ROM).unwrap();
+    run(&mut chip8);
+
+    let state = chip8.save_state();
+    let expected = run(&mut chip8);
+    chip8.set_seed(8);
+    chip8.load_state(&state).unwrap();
+    assert_eq!(run(&mut chip8), expected);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use chip8_core::{Emulator, RewindBuffer, Scheduler};
+use common::{load_rom, run_frame, TICKS_PER_FRAME};
+
+// The parts of the machine a rewind has to bring back.
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    ram: Vec<u8>,
+    registers: chip8_core::Registers,
+    stack: Vec<u16>,
+    pixels: Vec<u8>,
+}
+
+fn snapshot(chip8: &Emulator) -> Snapshot {
+    Snapshot {
+        ram: chip8.memory().to_vec(),
+        registers: chip8.registers(),
+        stack: chip8.stack().to_vec(),
+        pixels: chip8.get_display().pixels.to_vec(),
+    }
+}
+
+// Plays `frames` frames of Bug Game, moving the bug, pushing every frame to the buffer.
+// Returns the snapshot taken at each push.
+fn play(chip8: &mut Emulator, buffer: &mut RewindBuffer, frames: usize) -> Vec<Snapshot> {
+    let mut scheduler = Scheduler::new(TICKS_PER_FRAME * 60);
+    let mut snapshots = Vec::new();
+    for frame in 0..frames {
+        match frame % 40 {
+            10 => chip8.keypress(9, true).unwrap(),
+            30 => chip8.keypress(9, false).unwrap(),
+            _ => ()
+        }
+        run_frame(chip8, &mut scheduler);
+        buffer.push(chip8);
+        snapshots.push(snapshot(chip8));
+    }
+    snapshots
+}
+
+#[test]
+fn rewinding_restores_each_earlier_frame_exactly() {
+    let mut chip8 = load_rom("games/roms/bug_game.ch8", 1);
+    let mut buffer = RewindBuffer::new(100);
+    let snapshots = play(&mut chip8, &mut buffer, 80);
+    assert_eq!(buffer.len(), 79);
+
+    for frame in (60..79).rev() {
+        assert!(buffer.rewind(&mut chip8));
+        assert_eq!(snapshot(&chip8), snapshots[frame], "frame {}", frame);
+    }
+    assert_eq!(buffer.len(), 60);
+}
+
+#[test]
+fn eviction_keeps_the_oldest_frames_reconstructible() {
+    let mut chip8 = load_rom("games/roms/bug_game.ch8", 1);
+    let mut buffer = RewindBuffer::new(30);
+    let snapshots = play(&mut chip8, &mut buffer, 100);
+    assert_eq!(buffer.len(), 30);
+
+    while buffer.rewind(&mut chip8) {}
+    assert_eq!(snapshot(&chip8), snapshots[100 - 1 - 30]);
+}
+
+#[test]
+fn rewinding_keeps_the_keys_held_now() {
+    let mut chip8 = load_rom("games/roms/bug_game.ch8", 1);
+    let mut buffer = RewindBuffer::new(100);
+    // Key 9 is held from frame 10, and still at the last frame.
+    play(&mut chip8, &mut buffer, 20);
+
+    // Released before rewinding to frames it was held in.
+    chip8.keypress(9, false).unwrap();
+    assert!(buffer.rewind(&mut chip8));
+    assert!(!chip8.is_key_pressed(9));
+
+    // Held while rewinding to frames before it was pressed.
+    chip8.keypress(9, true).unwrap();
+    for _ in 0..10 {
+        assert!(buffer.rewind(&mut chip8));
+    }
+    assert!(chip8.is_key_pressed(9));
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use chip8_core::{run_frames, Chip8Error, KeyPress, Platform, Quirks, RunOutcome, Scheduler, StopReason};
+use common::{load, load_on};
+
+#[test]
+fn runs_every_frame_with_the_scripted_keys() {
+    // SKNP V0; ADD V1, 1; JP 200: counts the instructions run while key 0 is held.
+    let mut chip8 = load(Quirks::default(), &[0xE0A1, 0x7101, 0x1200]);
+    let mut frames_seen = 0;
+    let keys = [KeyPress { frame: 2, key: 0, hold: 1 }];
+    let (outcome, frames) = run_frames(&mut chip8, &mut Scheduler::new(180), 5, &keys, None, |_| frames_seen += 1);
+
+    assert_eq!((outcome, frames, frames_seen), (RunOutcome::FramesElapsed, 5, 5));
+    assert_eq!(chip8.registers().v[1], 1);
+    assert!(!chip8.is_key_pressed(0));
+}
+
+#[test]
+fn stops_in_the_frame_that_stopped() {
+    // ADD V0, 1; JP 200
+    let mut chip8 = load(Quirks::default(), &[0x7001, 0x1200]);
+    chip8.add_breakpoint(0x202);
+    let (outcome, frame) = run_frames(&mut chip8, &mut Scheduler::new(60), 10, &[], None, |_| ());
+    assert_eq!((outcome, frame), (RunOutcome::Stopped(StopReason::Breakpoint { addr: 0x202 }), 1));
+
+    // The timers don't tick in the frame that halted.
+    // LD V0, 5; LD DT, V0; EXIT
+    let mut chip8 = load_on(Platform::SuperChip, Quirks::SUPER_CHIP, &[0x6005, 0xF015, 0x00FD]);
+    let (outcome, frame) = run_frames(&mut chip8, &mut Scheduler::new(600), 10, &[], None, |_| ());
+    assert_eq!((outcome, frame), (RunOutcome::Halted, 0));
+    assert_eq!(chip8.registers().delay_timer, 5);
+
+    // RET with nothing on the stack.
+    let mut chip8 = load(Quirks::default(), &[0x00EE]);
+    let (outcome, _) = run_frames(&mut chip8, &mut Scheduler::new(60), 10, &[], None, |_| ());
+    assert_eq!(outcome, RunOutcome::Crashed(Chip8Error::StackUnderflow { pc: 0x200 }));
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use chip8_core::{Scheduler, MAX_SPEED, MIN_SPEED};
+
+#[test]
+fn runs_exact_rates_whatever_the_loop_rate() {
+    // 144 Hz display loop: 144 iterations make exactly one second.
+    let mut scheduler = Scheduler::new(700);
+    let (mut frames, mut instructions) = (0, 0);
+    for i in 0..144u64 {
+        let elapsed = Duration::from_nanos((i + 1) * 1_000_000_000 / 144 - i * 1_000_000_000 / 144);
+        for _ in 0..scheduler.advance(elapsed) {
+            frames += 1;
+            instructions += scheduler.frame_instructions().unwrap();
+        }
+    }
+
+    assert_eq!(frames, 60);
+    assert_eq!(instructions, 700);
+}
+
+#[test]
+fn drops_time_after_a_stall() {
+    let mut scheduler = Scheduler::new(600);
+    assert_eq!(scheduler.advance(Duration::from_millis(16)), 0);
+    assert!(scheduler.time_to_next_frame() <= Duration::from_micros(667));
+
+    assert_eq!(scheduler.advance(Duration::from_secs(5)), 6);
+    assert_eq!(scheduler.frame_instructions(), Some(10));
+}
+
+#[test]
+fn speed_scales_the_clock() {
+    let mut scheduler = Scheduler::new(480);
+    scheduler.set_speed(4.0);
+    assert_eq!((0..60).map(|_| scheduler.advance(Duration::from_nanos(16_666_667))).sum::<u32>(), 240);
+
+    scheduler.set_speed(0.5);
+    assert_eq!((0..60).map(|_| scheduler.advance(Duration::from_nanos(16_666_667))).sum::<u32>(), 30);
+}
+
+#[test]
+fn speed_is_kept_in_range() {
+    let mut scheduler = Scheduler::new(480);
+    for (speed, clamped) in [(0.0, MIN_SPEED), (-2.0, MIN_SPEED), (1e-300, MIN_SPEED), (1e300, MAX_SPEED),
+                             (f64::INFINITY, MAX_SPEED), (f64::NEG_INFINITY, MIN_SPEED), (f64::NAN, 1.0)] {
+        scheduler.set_speed(speed);
+        assert_eq!(scheduler.speed(), clamped, "{}", speed);
+        // Neither panics.
+        scheduler.advance(Duration::from_secs(1));
+        assert!(scheduler.time_to_next_frame() <= Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use chip8_core::{Emulator, Scheduler, StateError};
+use common::{load_rom, run_frame, TICKS_PER_FRAME};
+
+const HEADER_SIZE: usize = 4 + 2 + 4;
+
+// Bug Game part way through, with a key held.
+fn playing() -> Emulator {
+    let mut chip8 = load_rom("games/roms/bug_game.ch8", 1);
+    let mut scheduler = Scheduler::new(TICKS_PER_FRAME * 60);
+    for frame in 0..90 {
+        if frame == 60 {
+            chip8.keypress(9, true).unwrap();
+        }
+        run_frame(&mut chip8, &mut scheduler);
+    }
+    chip8
+}
+
+// FNV-1a, as the format checksums its payload.
+fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0x811C_9DC5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x0100_0193))
+}
+
+// Rebuilds a state with a different version and the payload cut to `payload_size` bytes,
+// with its length and checksum to match.
+fn rewrite(state: &[u8], version: u16, payload_size: usize) -> Vec<u8> {
+    let payload = &state[HEADER_SIZE..HEADER_SIZE + payload_size];
+    let mut data = state[..4].to_vec();
+    data.extend(version.to_be_bytes());
+    data.extend((payload.len() as u32).to_be_bytes());
+    data.extend(payload);
+    data.extend(checksum(payload).to_be_bytes());
+    data
+}
+
+fn payload_size(state: &[u8]) -> usize {
+    state.len() - HEADER_SIZE - 4
+}
+
+#[test]
+fn round_trip_restores_the_whole_machine() {
+    let mut chip8 = playing();
+    let state = chip8.save_state();
+
+    let mut restored = Emulator::new();
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.save_state(), state);
+    assert_eq!(restored.memory(), chip8.memory());
+    assert_eq!(restored.registers(), chip8.registers());
+    assert_eq!(restored.stack(), chip8.stack());
+    assert_eq!(restored.get_display().pixels, chip8.get_display().pixels);
+    assert!(restored.is_key_pressed(9));
+
+    // Both run on identically, random numbers included.
+    let mut scheduler = Scheduler::new(TICKS_PER_FRAME * 60);
+    let mut restored_scheduler = Scheduler::new(TICKS_PER_FRAME * 60);
+    for _ in 0..60 {
+        run_frame(&mut chip8, &mut scheduler);
+        run_frame(&mut restored, &mut restored_scheduler);
+    }
+    assert_eq!(restored.save_state(), chip8.save_state());
+}
+
+#[test]
+fn errors_leave_the_emulator_untouched() {
+    let state = playing().save_state();
+    let mut chip8 = Emulator::new();
+    let before = chip8.save_state();
+
+    let mut bad_magic = state.clone();
+    bad_magic[0] = b'X';
+    assert_eq!(chip8.load_state(&bad_magic), Err(StateError::BadMagic));
+    assert_eq!(chip8.load_state(b"C8"), Err(StateError::BadMagic));
+
+    let mut damaged = state.clone();
+    damaged[HEADER_SIZE + 100] ^= 0xFF;
+    assert_eq!(chip8.load_state(&damaged), Err(StateError::ChecksumMismatch));
+
+    for length in [6, HEADER_SIZE, state.len() / 2, state.len() - 1] {
+        assert_eq!(chip8.load_state(&state[..length]), Err(StateError::Truncated), "{} bytes", length);
+    }
+
+    // A consistent state whose payload stops early.
+    let short = rewrite(&state, 4, payload_size(&state) - 1);
+    assert_eq!(chip8.load_state(&short), Err(StateError::Truncated));
+
+    assert_eq!(chip8.save_state(), before);
+}
+
+#[test]
+fn unknown_versions_are_rejected() {
+    let state = playing().save_state();
+    let mut chip8 = Emulator::new();
+    let before = chip8.save_state();
+
+    for version in [0, 5, u16::MAX] {
+        let data = rewrite(&state, version, payload_size(&state));
+        assert_eq!(chip8.load_state(&data), Err(StateError::UnsupportedVersion(version)));
+    }
+    assert_eq!(chip8.save_state(), before);
+}
+
+#[test]
+fn older_versions_load_without_the_newer_fields() {
+    let state = playing().save_state();
+    // Version 4 appended the FX0A wait: a flag and 16 pairs of key flags.
+    let v3 = rewrite(&state, 3, payload_size(&state) - 33);
+    // Version 3 appended the COSMAC VIP cycle count.
+    let v2 = rewrite(&state, 2, payload_size(&state) - 33 - 4);
+
+    for older in [v3, v2] {
+        let mut chip8 = Emulator::new();
+        chip8.load_state(&older).unwrap();
+        assert_eq!(chip8.save_state(), state);
+    }
+}
+
+#[test]
+fn newer_payload_under_an_older_version_is_rejected() {
+    let state = playing().save_state();
+    let mut chip8 = Emulator::new();
+    let before = chip8.save_state();
+
+    let mislabelled = rewrite(&state, 3, payload_size(&state));
+    assert_eq!(chip8.load_state(&mislabelled), Err(StateError::Corrupt));
+    assert_eq!(chip8.save_state(), before);
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use chip8_core::{Emulator, Platform, Quirks};
+use common::{load_on, program, tick};
+
+// Address of the 16x16 sprite placed after the code by `load_with_sprite`.
+const SPRITE: u16 = 0x240;
+
+fn load(opcodes: &[u16]) -> Emulator {
+    load_on(Platform::SuperChip, Quirks::SUPER_CHIP, opcodes)
+}
+
+// Loads the code followed, at SPRITE, by a solid 16x16 sprite.
+fn load_with_sprite(opcodes: &[u16]) -> Emulator {
+    let mut data = program(opcodes);
+    data.resize((SPRITE - 0x200) as usize, 0);
+    data.extend([0xFF; 32]);
+    let mut chip8 = Emulator::with_platform(Platform::SuperChip, Quirks::SUPER_CHIP);
+    chip8.load_data(&data).unwrap();
+    chip8
+}
+
+fn lit_pixels(chip8: &Emulator) -> Vec<(usize, usize)> {
+    let display = chip8.get_display();
+    (0..display.pixels.len())
+        .filter(|&index| display.pixels[index] != 0)
+        .map(|index| (index % display.width, index / display.width))
+        .collect()
+}
+
+// HIGH; LD V0, x; LD V1, y; LD F, V2 (digit 0); DRW V0, V1, 5; then the scroll.
+fn draw_zero_and_scroll(x: u8, y: u8, scroll: u16) -> Emulator {
+    let mut chip8 = load(&[0x00FF, 0x6000 | x as u16, 0x6100 | y as u16, 0xF229, 0xD015, scroll]);
+    tick(&mut chip8, 5);
+    assert!(!lit_pixels(&chip8).is_empty());
+    tick(&mut chip8, 1);
+    chip8
+}
+
+#[test]
+fn scrolling_moves_pixels_by_the_current_resolution() {
+    // The top row of the 0 is 4 pixels wide, at x 0-3 before scrolling right.
+    let chip8 = draw_zero_and_scroll(0, 0, 0x00FB);
+    let top_row: Vec<_> = lit_pixels(&chip8).into_iter().filter(|&(_, y)| y == 0).collect();
+    assert_eq!(top_row, [(4, 0), (5, 0), (6, 0), (7, 0)]);
+
+    let chip8 = draw_zero_and_scroll(0, 0, 0x00C3);
+    assert_eq!(lit_pixels(&chip8).iter().map(|&(_, y)| y).min(), Some(3));
+}
+
+#[test]
+fn pixels_scrolled_past_the_edges_are_cleared() {
+    // Right from the right edge, left from the left edge, down from the bottom edge.
+    assert!(lit_pixels(&draw_zero_and_scroll(124, 0, 0x00FB)).is_empty());
+    assert!(lit_pixels(&draw_zero_and_scroll(0, 0, 0x00FC)).is_empty());
+    assert!(lit_pixels(&draw_zero_and_scroll(0, 59, 0x00C5)).is_empty());
+}
+
+#[test]
+fn switching_resolution_clears_the_screen() {
+    // LD F, V2; DRW V0, V1, 5; HIGH; DRW V0, V1, 5; LOW
+    let mut chip8 = load(&[0xF229, 0xD015, 0x00FF, 0xD015, 0x00FE]);
+    tick(&mut chip8, 2);
+    assert_eq!(lit_pixels(&chip8).len(), 14);
+
+    tick(&mut chip8, 1);
+    assert_eq!((chip8.get_display().width, chip8.get_display().height), (128, 64));
+    assert!(lit_pixels(&chip8).is_empty());
+
+    tick(&mut chip8, 1);
+    assert_eq!(lit_pixels(&chip8).len(), 14);
+    tick(&mut chip8, 1);
+    assert_eq!((chip8.get_display().width, chip8.get_display().height), (64, 32));
+    assert!(lit_pixels(&chip8).is_empty());
+}
+
+#[test]
+fn dxy0_draws_16x16_sprites() {
+    // LD I, SPRITE; DRW V0, V1, 0; DRW V0, V1, 0
+    let mut chip8 = load_with_sprite(&[0xA000 | SPRITE, 0xD010, 0xD010]);
+    tick(&mut chip8, 2);
+    assert_eq!(lit_pixels(&chip8).len(), 256);
+    assert_eq!(chip8.registers().v[0xF], 0);
+
+    // In low resolution a collision sets VF to 1.
+    tick(&mut chip8, 1);
+    assert!(lit_pixels(&chip8).is_empty());
+    assert_eq!(chip8.registers().v[0xF], 1);
+}
+
+#[test]
+fn dxy0_counts_collided_rows_in_high_resolution() {
+    // HIGH; LD I, SPRITE; DRW V0, V1, 0; DRW V0, V1, 0
+    let mut chip8 = load_with_sprite(&[0x00FF, 0xA000 | SPRITE, 0xD010, 0xD010]);
+    tick(&mut chip8, 3);
+    assert_eq!(chip8.registers().v[0xF], 0);
+    tick(&mut chip8, 1);
+    assert_eq!(chip8.registers().v[0xF], 16);
+
+    // Only the 5 rows of a 0 drawn first collide.
+    // HIGH; LD F, V2; DRW V0, V1, 5; LD I, SPRITE; DRW V0, V1, 0
+    let mut chip8 = load_with_sprite(&[0x00FF, 0xF229, 0xD015, 0xA000 | SPRITE, 0xD010]);
+    tick(&mut chip8, 5);
+    assert_eq!(chip8.registers().v[0xF], 5);
+}
+
+#[test]
+fn dxy0_counts_rows_clipped_by_the_bottom_edge() {
+    // HIGH; LD V1, 56; LD I, SPRITE; DRW V0, V1, 0; DRW V0, V1, 0
+    let mut chip8 = load_with_sprite(&[0x00FF, 0x6138, 0xA000 | SPRITE, 0xD010, 0xD010]);
+    tick(&mut chip8, 4);
+    assert_eq!(lit_pixels(&chip8).len(), 16 * 8);
+    assert_eq!(chip8.registers().v[0xF], 8);
+
+    tick(&mut chip8, 1);
+    assert_eq!(chip8.registers().v[0xF], 16);
+}
+
+#[test]
+fn row_count_needs_the_quirk() {
+    // HIGH; LD I, SPRITE; DRW V0, V1, 0; DRW V0, V1, 0
+    let mut chip8 = load_with_sprite(&[0x00FF, 0xA000 | SPRITE, 0xD010, 0xD010]);
+    chip8.set_quirks(Quirks { vf_row_count: false, ..Quirks::SUPER_CHIP });
+    tick(&mut chip8, 4);
+    assert_eq!(chip8.registers().v[0xF], 1);
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use chip8_core::{Emulator, Quirks, Scheduler, Timing};
+
+fn load(program: &[u16]) -> Emulator {
+    common::load(Quirks::COSMAC_VIP, program)
+}
+
+// Runs one frame and returns V0, which the programs count loop iterations in.
+fn run_frame(chip8: &mut Emulator) -> u8 {
+    common::run_frame(chip8, &mut Scheduler::with_timing(Timing::CosmacVip));
+    chip8.registers().v[0]
+}
+
+#[test]
+fn costly_instructions_run_fewer_per_frame() {
+    // ADD V0, 1; JP 200
+    let cheap = run_frame(&mut load(&[0x7001, 0x1200]));
+    // CLS; ADD V0, 1; JP 200
+    let clearing = run_frame(&mut load(&[0x00E0, 0x7001, 0x1200]));
+    assert!(cheap > 10);
+    assert!(clearing > 0 && clearing < cheap / 4);
+}
+
+#[test]
+fn drawing_waits_for_the_vertical_blank() {
+    // LD I, 0; DRW V1, V1, 15; ADD V0, 1; JP 202
+    let mut chip8 = load(&[0xA000, 0xD11F, 0x7001, 0x1202]);
+    chip8.run_vip_frame().unwrap();
+    assert_eq!(chip8.registers().pc, 0x204);
+
+    // Every frame after runs the loop once and draws again.
+    chip8.tick_timers();
+    assert_eq!(run_frame(&mut chip8), 1);
+    assert_eq!(run_frame(&mut chip8), 2);
+}
+
+#[test]
+fn cycles_survive_save_states() {
+    // LD I, 0; DRW V1, V1, 15; then ADD V0, 1; JP 204, after the cycles owed for the sprite.
+    let mut chip8 = load(&[0xA000, 0xD11F, 0x7001, 0x1204]);
+    run_frame(&mut chip8);
+    let state = chip8.save_state();
+    let expected = run_frame(&mut chip8.clone());
+
+    let mut restored = load(&[]);
+    restored.load_state(&state).unwrap();
+    assert_eq!(run_frame(&mut restored), expected);
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use chip8_core::{Emulator, Platform, Quirks};
+use common::{load_on, program, tick};
+
+// Address of the sprite data placed after the code by `load_with_data`.
+const DATA: u16 = 0x240;
+
+fn load(platform: Platform, opcodes: &[u16]) -> Emulator {
+    load_on(platform, platform.default_quirks(), opcodes)
+}
+
+fn load_with_data(opcodes: &[u16], data: &[u8]) -> Emulator {
+    let mut rom = program(opcodes);
+    rom.resize((DATA - 0x200) as usize, 0);
+    rom.extend(data);
+    let mut chip8 = Emulator::with_platform(Platform::XoChip, Quirks::XO_CHIP);
+    chip8.load_data(&rom).unwrap();
+    chip8
+}
+
+// The first row of the screen, as the plane bits of its first 8 pixels.
+fn first_pixels(chip8: &Emulator) -> [u8; 8] {
+    chip8.get_display().pixels[..8].try_into().unwrap()
+}
+
+#[test]
+fn skips_step_over_the_whole_long_load() {
+    // Setup, then a skip that skips, followed by LD I, 1234 (F000 1234).
+    let cases: [(&[u16], u16); 5] = [
+        (&[], 0x3000),       // SE V0, 0
+        (&[], 0x4001),       // SNE V0, 1
+        (&[], 0x5010),       // SE V0, V1
+        (&[0x6101], 0x9010), // LD V1, 1; SNE V0, V1
+        (&[], 0xE0A1)        // SKNP V0
+    ];
+    for (setup, skip) in cases {
+        let mut opcodes = setup.to_vec();
+        opcodes.extend([skip, 0xF000, 0x1234]);
+        let mut chip8 = load(Platform::XoChip, &opcodes);
+        tick(&mut chip8, setup.len() + 1);
+        assert_eq!(chip8.registers().pc, 0x206 + 2 * setup.len(), "{:04X}", skip);
+        assert_ne!(chip8.registers().i, 0x1234);
+    }
+
+    // SKP V0 with key 0 held.
+    let mut chip8 = load(Platform::XoChip, &[0xE09E, 0xF000, 0x1234]);
+    chip8.keypress(0, true).unwrap();
+    tick(&mut chip8, 1);
+    assert_eq!(chip8.registers().pc, 0x206);
+}
+
+#[test]
+fn skips_that_dont_skip_run_the_long_load() {
+    // SE V0, 1; LD I, 1234
+    let mut chip8 = load(Platform::XoChip, &[0x3001, 0xF000, 0x1234]);
+    tick(&mut chip8, 2);
+    assert_eq!(chip8.registers().pc, 0x206);
+    assert_eq!(chip8.registers().i, 0x1234);
+}
+
+#[test]
+fn skips_before_xo_chip_skip_two_bytes() {
+    // SE V0, 0; then F000 is not an instruction of SUPER-CHIP.
+    let mut chip8 = load(Platform::SuperChip, &[0x3000, 0xF000, 0x1234]);
+    tick(&mut chip8, 1);
+    assert_eq!(chip8.registers().pc, 0x204);
+}
+
+#[test]
+fn register_ranges_run_from_x_to_y_when_x_is_greater() {
+    // LD I, 300; LD V1, 1; LD V2, 2; LD V3, 3; LD [I], V3-V1
+    let mut chip8 = load(Platform::XoChip, &[0xA300, 0x6101, 0x6202, 0x6303, 0x5312]);
+    tick(&mut chip8, 5);
+    assert_eq!(chip8.memory()[0x300..0x303], [3, 2, 1]);
+    assert_eq!(chip8.registers().i, 0x300);
+
+    // LD I, 300; LD V1, 1; LD V2, 2; LD V3, 3; LD [I], V1-V3; LD V3-V1, [I]
+    let mut chip8 = load(Platform::XoChip, &[0xA300, 0x6101, 0x6202, 0x6303, 0x5132, 0x5313]);
+    tick(&mut chip8, 6);
+    assert_eq!(chip8.registers().v[1..4], [3, 2, 1]);
+    assert_eq!(chip8.registers().i, 0x300);
+}
+
+#[test]
+fn drawing_on_plane_2() {
+    // PLANE 2; LD I, DATA; DRW V0, V0, 1; DRW V0, V0, 1
+    let mut chip8 = load_with_data(&[0xF201, 0xA000 | DATA, 0xD001, 0xD001], &[0xF0]);
+    tick(&mut chip8, 3);
+    assert_eq!(first_pixels(&chip8), [2, 2, 2, 2, 0, 0, 0, 0]);
+    assert_eq!(chip8.registers().v[0xF], 0);
+
+    tick(&mut chip8, 1);
+    assert_eq!(first_pixels(&chip8), [0; 8]);
+    assert_eq!(chip8.registers().v[0xF], 1);
+}
+
+#[test]
+fn drawing_on_both_planes() {
+    // The sprite holds the row for plane 1, then the row for plane 2.
+    // PLANE 3; LD I, DATA; DRW V0, V0, 1; DRW V0, V0, 1
+    let opcodes = [0xF301, 0xA000 | DATA, 0xD001, 0xD001];
+    let mut chip8 = load_with_data(&opcodes, &[0xF0, 0x3C]);
+    tick(&mut chip8, 3);
+    assert_eq!(first_pixels(&chip8), [1, 1, 3, 3, 2, 2, 0, 0]);
+    assert_eq!(chip8.registers().v[0xF], 0);
+
+    // Drawing again erases pixels on both planes.
+    tick(&mut chip8, 1);
+    assert_eq!(first_pixels(&chip8), [0; 8]);
+    assert_eq!(chip8.registers().v[0xF], 1);
+}
+
+#[test]
+fn collisions_are_per_plane() {
+    // PLANE 2; LD I, DATA; DRW V0, V0, 1: plane 2 pixels at x 0-3.
+    // PLANE 1; LD I, DATA+1; DRW V0, V0, 1: plane 1 pixels at x 2-5, over plane 2 only.
+    let opcodes = [0xF201, 0xA000 | DATA, 0xD001, 0xF101, 0xA001 | DATA, 0xD001];
+    let mut chip8 = load_with_data(&opcodes, &[0xF0, 0x3C]);
+    tick(&mut chip8, 6);
+    assert_eq!(first_pixels(&chip8), [2, 2, 3, 3, 1, 1, 0, 0]);
+    assert_eq!(chip8.registers().v[0xF], 0);
+}
@@ -0,0 +1,146 @@
+// Buzzer: a tone generator played through SDL audio while the sound timer is running.
+// XO-CHIP programs set their own sound instead: a 128-bit pattern, played back bit by bit.
+//
+// The device plays continuously and the tone is faded in and out over a few milliseconds,
+// so starting and stopping the beep doesn't click.
+
+use std::f32::consts::TAU;
+use sdl2::{audio::{AudioCallback, AudioDevice, AudioSpecDesired}, Sdl};
+
+const SAMPLE_RATE: i32 = 44100;
+const FADE_SECONDS: f32 = 0.005;
+const PATTERN_BITS: f32 = 128.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Waveform> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeepSettings {
+    pub waveform: Waveform,
+    // Tone frequency in Hz.
+    pub frequency: f32,
+    // 0.0 (silent) to 1.0 (full scale).
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for BeepSettings {
+    fn default() -> Self {
+        BeepSettings {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.25,
+            muted: false
+        }
+    }
+}
+
+struct ToneGenerator {
+    waveform: Waveform,
+    phase: f32,
+    phase_step: f32,
+    // XO-CHIP audio pattern, played instead of the waveform once set, with the position
+    // in it in bits and the bits played per sample.
+    pattern: Option<[u8; 16]>,
+    pattern_position: f32,
+    pattern_step: f32,
+    volume: f32,
+    // Current and wanted amplitude; gain moves towards target by fade_step per sample.
+    gain: f32,
+    target: f32,
+    fade_step: f32,
+}
+
+impl AudioCallback for ToneGenerator {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            if self.gain < self.target {
+                self.gain = (self.gain + self.fade_step).min(self.target);
+            } else if self.gain > self.target {
+                self.gain = (self.gain - self.fade_step).max(self.target);
+            }
+
+            let wave = match (self.pattern, self.waveform) {
+                (Some(pattern), _) => {
+                    let bit = self.pattern_position as usize;
+                    if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 { 1.0 } else { -1.0 }
+                },
+                (None, Waveform::Square) => if self.phase < 0.5 { 1.0 } else { -1.0 },
+                (None, Waveform::Sine) => (self.phase * TAU).sin(),
+            };
+            *sample = wave * self.gain * self.volume;
+            self.phase = (self.phase + self.phase_step) % 1.0;
+            self.pattern_position = (self.pattern_position + self.pattern_step) % PATTERN_BITS;
+        }
+    }
+}
+
+pub struct Beeper {
+    device: AudioDevice<ToneGenerator>,
+    muted: bool,
+}
+
+impl Beeper {
+    pub fn new(sdl_context: &Sdl, settings: BeepSettings) -> Result<Beeper, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: Some(512)
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            ToneGenerator {
+                waveform: settings.waveform,
+                phase: 0.0,
+                phase_step: settings.frequency / spec.freq as f32,
+                pattern: None,
+                pattern_position: 0.0,
+                pattern_step: 0.0,
+                volume: settings.volume.clamp(0.0, 1.0),
+                gain: 0.0,
+                target: 0.0,
+                fade_step: 1.0 / (FADE_SECONDS * spec.freq as f32)
+            }
+        })?;
+        device.resume();
+
+        Ok(Beeper { device, muted: settings.muted })
+    }
+
+    // Starts or stops the tone; call once per frame with Emulator::is_sound_active.
+    pub fn set_active(&mut self, active: bool) {
+        self.device.lock().target = if active && !self.muted { 1.0 } else { 0.0 };
+    }
+
+    // Plays an XO-CHIP audio pattern at a rate in bits per second instead of the tone;
+    // call once per frame with Emulator::audio_pattern and Emulator::audio_playback_rate.
+    pub fn set_pattern(&mut self, pattern: &[u8; 16], rate: f32) {
+        let sample_rate = self.device.spec().freq as f32;
+        let mut generator = self.device.lock();
+        // Until F002 loads a pattern (it starts out silent), the buzzer keeps its tone.
+        generator.pattern = Some(*pattern).filter(|pattern| pattern.iter().any(|byte| *byte != 0));
+        generator.pattern_step = rate / sample_rate;
+    }
+
+    // Returns true if the beeper is now muted.
+    pub fn toggle_mute(&mut self) -> bool {
+        self.muted = !self.muted;
+        self.muted
+    }
+}
@@ -0,0 +1,359 @@
+// Command line parsing.
+
+use chip8_core::{parse_address, Platform, Quirks, RomSettings, Syntax, Timing, WatchKind, Watchpoint, MAX_SPEED, MIN_SPEED, TIMER_HZ};
+use sdl2::pixels::Color;
+
+use crate::audio::{BeepSettings, Waveform};
+use crate::keymap::{Keymap, LAYOUT_NAMES};
+
+pub const USAGE: &str = "\
+Usage: desktop [OPTIONS] [ROM | FOLDER]
+       desktop disasm ROM [--octo]
+
+Runs a CHIP-8, SUPER-CHIP (.sc8) or XO-CHIP (.xo8) ROM. Given a folder, or nothing,
+lists the ROMs to pick from.
+
+Defaults for the options, the ROM folder and the keypad keys are read from
+$XDG_CONFIG_HOME/chip8/config.toml (~/.config/chip8/config.toml), which is written
+with comments on first run. Known ROMs get their platform, quirks, speed, palette and
+arrow/Space/Shift keys from a ROM database, extended by programs.json (in the layout of
+the community chip-8-database) in the same folder. Options given here take precedence.
+
+Options:
+  --scale N               Window pixels per CHIP-8 pixel (default 15)
+  --cpu-hz N              Instructions executed per second (default 480)
+  --ticks-per-frame N     Instructions executed per 1/60 second, instead of --cpu-hz
+  --vip-timing            Give instructions the time they took on the COSMAC VIP, instead of --cpu-hz
+  --speed X               Run X times as fast as real time, e.g. 0.5 for slow motion (default 1)
+  --fast-forward X|max    Speed while Tab is held (default max: as fast as possible)
+  --paused                Start paused
+  --quirks PRESET         vip, chip48, schip or xochip (default: the ROM platform's)
+  --display-wait on|off   Whether DXYN waits for the vertical blank (default: the quirks')
+  --key-wait press|release
+                          Whether FX0A takes a held key or waits for its release (default release)
+  --palette PALETTE       mono, green or amber, or 2 or 4 comma-separated RRGGBB colours
+  --fullscreen            Start in fullscreen
+  --layout LAYOUT         Keypad keys for a qwerty, azerty, qwertz, dvorak or colemak keyboard
+  --mute                  Start with sound muted
+  --beep WAVEFORM         square or sine (default square)
+  --beep-freq HZ          Beep frequency (default 440)
+  --volume PERCENT        Beep volume, 0 to 100 (default 25)
+  --seed N                Seed for the random numbers of CXKK (default: random)
+  --headless              Run without a window and print the final screen
+  --frames N              Frames to run with --headless (default 600)
+  --play MOVIE            Play back a movie recorded with F3, with its recorded timing
+  --break ADDR            Pause when PC reaches the hex address ADDR
+  --watch RANGE[:r|w|rw]  Log reads and/or writes to a hex address range, e.g. 0x300-0x31F:w
+  --watch-break RANGE[:r|w|rw]
+                          Like --watch, but also pause
+  --help                  Print this message
+
+Keys:
+  1 2 3 4 / Q W E R / A S D F / Z X C V  CHIP-8 keypad (by default)
+  F1 pause/resume, \\ advance one frame, Tab fast-forward (hold), - / = halve / double the speed
+  Game controller: D-pad or left stick 5 7 8 9, A 6, B 4, X E, Y D (by default)
+  Esc quit, F2 toggle breakpoint at PC, F10 step over, F11 step, F12 run to return,
+  F3 record movie, F4 mute, F5 save state, F9 load state, F6/F7 select save slot, Backspace rewind,
+  F8 set the keypad keys of the running ROM
+
+Exit codes: 0 on success, 1 on errors while running, 2 on bad arguments.";
+
+pub const DEFAULT_SCALE: u32 = 15;
+pub const DEFAULT_CPU_HZ: u32 = 480;
+pub const DEFAULT_HEADLESS_FRAMES: usize = 600;  // 10 seconds at 60 fps
+
+// Colours for each pixel value; XO-CHIP's two bitplanes give 4 colours.
+const MONO_PALETTE: [Color; 4] = [
+    Color::RGB(0, 0, 0),
+    Color::RGB(255, 255, 255),
+    Color::RGB(170, 170, 170),
+    Color::RGB(85, 85, 85)
+];
+const GREEN_PALETTE: [Color; 4] = [
+    Color::RGB(15, 56, 15),
+    Color::RGB(155, 188, 15),
+    Color::RGB(139, 172, 15),
+    Color::RGB(48, 98, 48)
+];
+const AMBER_PALETTE: [Color; 4] = [
+    Color::RGB(40, 20, 0),
+    Color::RGB(255, 176, 0),
+    Color::RGB(204, 122, 0),
+    Color::RGB(128, 64, 0)
+];
+
+pub enum Command {
+    Run(Box<Options>),
+    Disassemble { rom_path: String, syntax: Syntax },
+    Help,
+}
+
+// Settings given on the command line, which ROM database entries don't replace.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Given {
+    pub quirks: bool,
+    pub cpu_hz: bool,
+    pub palette: bool,
+    pub keymap: bool,
+}
+
+pub struct Options {
+    // ROM file, or folder to pick one from.
+    pub rom_path: Option<String>,
+    // Folder to pick a ROM from when none is given.
+    pub rom_dir: Option<String>,
+    pub scale: u32,
+    // None to pick the platform from the ROM's file extension.
+    pub platform: Option<Platform>,
+    // Instructions per second. The timers run at 60 Hz.
+    pub cpu_hz: u32,
+    // Run each frame's instructions for the machine cycles of a COSMAC VIP frame, instead of cpu_hz.
+    pub vip_timing: bool,
+    // Emulated time per real time.
+    pub speed: f64,
+    // Speed while fast-forwarding, or None to run as fast as possible.
+    pub fast_forward: Option<f64>,
+    pub start_paused: bool,
+    pub quirks: Option<Quirks>,
+    // Overrides the display wait quirk of the quirks.
+    pub display_wait: Option<bool>,
+    // FX0A takes a held key, instead of waiting for it to be released.
+    pub key_wait_on_press: bool,
+    pub palette: [Color; 4],
+    pub fullscreen: bool,
+    pub beep: BeepSettings,
+    pub seed: Option<u64>,
+    pub headless: bool,
+    pub frames: usize,
+    pub movie_path: Option<String>,
+    pub breakpoints: Vec<usize>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub keymap: Keymap,
+    pub given: Given,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            rom_path: None,
+            rom_dir: None,
+            scale: DEFAULT_SCALE,
+            platform: None,
+            cpu_hz: DEFAULT_CPU_HZ,
+            vip_timing: false,
+            speed: 1.0,
+            fast_forward: None,
+            start_paused: false,
+            quirks: None,
+            display_wait: None,
+            key_wait_on_press: false,
+            palette: MONO_PALETTE,
+            fullscreen: false,
+            beep: BeepSettings::default(),
+            seed: None,
+            headless: false,
+            frames: DEFAULT_HEADLESS_FRAMES,
+            movie_path: None,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            keymap: Keymap::default(),
+            given: Given::default()
+        }
+    }
+}
+
+impl Options {
+    pub fn timing(&self) -> Timing {
+        if self.vip_timing { Timing::CosmacVip } else { Timing::CpuHz(self.cpu_hz) }
+    }
+
+    pub fn rom_settings(&self) -> RomSettings {
+        RomSettings {
+            platform: self.platform,
+            quirks: self.quirks,
+            display_wait: self.display_wait,
+            key_wait_on_press: self.key_wait_on_press,
+            seed: self.seed
+        }
+    }
+}
+
+// Parses a speed such as `2` or `0.5`.
+pub fn parse_speed(text: &str) -> Option<f64> {
+    text.parse().ok().filter(|speed| (MIN_SPEED..=MAX_SPEED).contains(speed))
+}
+
+// Parses a watchpoint such as `0x300-0x31F:w`: an address or inclusive range,
+// optionally followed by `:r`, `:w` or `:rw` (the default).
+fn parse_watchpoint(text: &str, pause: bool) -> Option<Watchpoint> {
+    let (range, kind) = text.split_once(':').unwrap_or((text, "rw"));
+    let kind = match kind {
+        "r" => WatchKind::Read,
+        "w" => WatchKind::Write,
+        "rw" => WatchKind::ReadWrite,
+        _ => return None
+    };
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let addresses = parse_address(start)?..=parse_address(end)?;
+
+    Some(Watchpoint { addresses, kind, pause })
+}
+
+// Parses a palette name, or 2 or 4 colours such as `000000,FFFFFF`.
+// With 2 colours, the XO-CHIP colours are blends of the two.
+pub fn parse_palette(text: &str) -> Option<[Color; 4]> {
+    match text {
+        "mono" => return Some(MONO_PALETTE),
+        "green" => return Some(GREEN_PALETTE),
+        "amber" => return Some(AMBER_PALETTE),
+        _ => ()
+    }
+
+    let colors = text.split(',')
+        .map(|hex| {
+            let hex = hex.trim().trim_start_matches('#');
+            let rgb = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+            Some(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+        })
+        .collect::<Option<Vec<Color>>>()?;
+
+    let blend = |a: Color, b: Color, weight: u16| Color::RGB(
+        ((a.r as u16 * (3 - weight) + b.r as u16 * weight) / 3) as u8,
+        ((a.g as u16 * (3 - weight) + b.g as u16 * weight) / 3) as u8,
+        ((a.b as u16 * (3 - weight) + b.b as u16 * weight) / 3) as u8
+    );
+    match colors[..] {
+        [off, on] => Some([off, on, blend(off, on, 2), blend(off, on, 1)]),
+        [c0, c1, c2, c3] => Some([c0, c1, c2, c3]),
+        _ => None
+    }
+}
+
+fn parse_disasm(args: &[String]) -> Result<Command, String> {
+    let mut rom_path = None;
+    let mut syntax = Syntax::Cowgod;
+    for arg in args {
+        match arg.as_str() {
+            "--octo" => syntax = Syntax::Octo,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ if rom_path.is_none() => rom_path = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument {}", arg))
+        }
+    }
+
+    let rom_path = rom_path.ok_or("disasm expects a ROM")?;
+    Ok(Command::Disassemble { rom_path, syntax })
+}
+
+// Parses the command line, with options not given on it taken from `defaults`.
+pub fn parse_args(args: Vec<String>, defaults: Options) -> Result<Command, String> {
+    if args.first().map(String::as_str) == Some("disasm") {
+        return parse_disasm(&args[1..]);
+    }
+
+    let mut options = defaults;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} expects a value", arg));
+        match arg.as_str() {
+            "--help" | "-h" => return Ok(Command::Help),
+            "--scale" => {
+                options.scale = value()?.parse().ok().filter(|scale| *scale > 0)
+                    .ok_or("--scale expects a positive number")?;
+            },
+            "--cpu-hz" => {
+                options.cpu_hz = value()?.parse().ok().filter(|hz| *hz > 0)
+                    .ok_or("--cpu-hz expects a positive number")?;
+                options.given.cpu_hz = true;
+            },
+            "--ticks-per-frame" => {
+                let ticks: u32 = value()?.parse().ok().filter(|ticks| *ticks > 0)
+                    .ok_or("--ticks-per-frame expects a positive number")?;
+                options.cpu_hz = ticks.saturating_mul(TIMER_HZ);
+                options.given.cpu_hz = true;
+            },
+            "--vip-timing" => options.vip_timing = true,
+            "--speed" => {
+                options.speed = parse_speed(&value()?)
+                    .ok_or(format!("--speed expects a speed from {} to {}, e.g. --speed 0.5", MIN_SPEED, MAX_SPEED))?;
+            },
+            "--fast-forward" => {
+                options.fast_forward = match value()?.as_str() {
+                    "max" => None,
+                    speed => Some(parse_speed(speed)
+                        .ok_or(format!("--fast-forward expects max or a speed from {} to {}", MIN_SPEED, MAX_SPEED))?)
+                };
+            },
+            "--paused" => options.start_paused = true,
+            "--quirks" => {
+                let name = value()?;
+                options.quirks = Some(Quirks::from_preset_name(&name)
+                    .ok_or(format!("Unknown quirks preset `{}`, expected one of {}", name, Quirks::PRESET_NAMES.join(", ")))?);
+                options.given.quirks = true;
+            },
+            "--display-wait" => {
+                options.display_wait = Some(match value()?.as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(String::from("--display-wait expects on or off"))
+                });
+            },
+            "--key-wait" => {
+                options.key_wait_on_press = match value()?.as_str() {
+                    "press" => true,
+                    "release" => false,
+                    _ => return Err(String::from("--key-wait expects press or release"))
+                };
+            },
+            "--palette" => {
+                options.palette = parse_palette(&value()?)
+                    .ok_or("--palette expects mono, green, amber or colours such as 000000,FFFFFF")?;
+                options.given.palette = true;
+            },
+            "--layout" => {
+                let name = value()?;
+                options.keymap = Keymap::from_layout(&name)
+                    .ok_or(format!("Unknown layout `{}`, expected one of {}", name, LAYOUT_NAMES.join(", ")))?;
+                options.given.keymap = true;
+            },
+            "--fullscreen" => options.fullscreen = true,
+            "--mute" => options.beep.muted = true,
+            "--beep" => {
+                options.beep.waveform = Waveform::from_name(&value()?).ok_or("--beep expects square or sine")?;
+            },
+            "--beep-freq" => {
+                options.beep.frequency = value()?.parse().ok().filter(|hz: &f32| *hz > 0.0)
+                    .ok_or("--beep-freq expects a frequency in Hz, e.g. --beep-freq 440")?;
+            },
+            "--volume" => {
+                let volume: u8 = value()?.parse().ok().filter(|volume| *volume <= 100)
+                    .ok_or("--volume expects a percentage from 0 to 100")?;
+                options.beep.volume = volume as f32 / 100.0;
+            },
+            "--seed" => {
+                options.seed = Some(value()?.parse().map_err(|_| "--seed expects a number")?);
+            },
+            "--headless" => options.headless = true,
+            "--frames" => {
+                options.frames = value()?.parse().map_err(|_| "--frames expects a number")?;
+            },
+            "--play" => options.movie_path = Some(value()?),
+            "--break" => {
+                options.breakpoints.push(parse_address(&value()?).ok_or("--break expects a hex address, e.g. --break 0x2A4")?);
+            },
+            "--watch" | "--watch-break" => {
+                let watchpoint = parse_watchpoint(&value()?, arg == "--watch-break")
+                    .ok_or(format!("{} expects an address range, e.g. {} 0x300-0x31F:w", arg, arg))?;
+                options.watchpoints.push(watchpoint);
+            },
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ if options.rom_path.is_none() => options.rom_path = Some(arg),
+            _ => return Err(format!("Unexpected argument {}", arg))
+        }
+    }
+
+    if options.headless && options.rom_path.is_none() {
+        return Err(String::from("--headless needs a ROM"));
+    }
+    Ok(Command::Run(Box::new(options)))
+}
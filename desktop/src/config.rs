@@ -0,0 +1,506 @@
+// Configuration file: $XDG_CONFIG_HOME/chip8/config.toml (or ~/.config/chip8/config.toml).
+//
+// The file provides the defaults of the command line options; options given on the
+// command line override it. A commented default file is written the first time it is missing.
+//
+// Only the part of TOML the file needs is supported: [sections], `key = value` lines,
+// strings, integers, floats, booleans, single-line arrays and # comments.
+
+use std::{env, fs, path::Path, path::PathBuf};
+
+use chip8_core::{Quirks, MAX_SPEED, MIN_SPEED};
+use sdl2::{controller::Button, keyboard::Keycode};
+
+use crate::audio::Waveform;
+use crate::cli::{parse_palette, Options};
+use crate::keymap::{Keymap, LAYOUT_NAMES, NUM_CHIP8_KEYS};
+
+const DEFAULT_CONFIG: &str = r#"# CHIP-8 emulator configuration.
+# Options given on the command line override these settings.
+
+# Folder to pick a ROM from when none is given on the command line.
+# Empty for the current folder.
+rom_dir = ""
+
+[display]
+# Window pixels per CHIP-8 pixel.
+scale = 15
+# mono, green, amber, or 2 or 4 colours such as "000000,FFFFFF".
+palette = "mono"
+fullscreen = false
+
+[speed]
+# Instructions executed per second. The timers always run at 60 Hz.
+cpu_hz = 480
+# Give each instruction the time it took on the COSMAC VIP instead, so drawing a sprite
+# takes far longer than a jump.
+vip_timing = false
+# Times real time, e.g. 0.5 for slow motion.
+speed = 1
+# Speed while Tab is held, or "max" to run as fast as possible.
+fast_forward = "max"
+
+[audio]
+muted = false
+# square or sine.
+waveform = "square"
+# Beep frequency in Hz.
+frequency = 440
+# 0 to 100.
+volume = 25
+
+[emulation]
+# Quirks preset: vip, chip48, schip or xochip. Empty to use the ROM platform's.
+quirks = ""
+# Whether DXYN waits for the vertical blank, limiting drawing to 60 sprites a second,
+# whatever the quirks say.
+# display_wait = true
+# Whether FX0A takes a key as soon as it is held ("press"), instead of when it is released.
+key_wait = "release"
+
+[keys]
+# Preset layout of the keypad on the left of the keyboard:
+# qwerty, azerty, qwertz, dvorak or colemak.
+layout = "qwerty"
+# Host keys for a CHIP-8 key by SDL key name, replacing the ones of the layout. For example,
+# to also play games that move with 2/4/6/8 (up, left, right, down) on the arrow keys:
+# 2 = ["2", "Up"]
+# 4 = ["Q", "Left"]
+# 6 = ["E", "Right"]
+# 8 = ["S", "Down"]
+# Keys for a single ROM are set with F8 while it runs, and saved in the keys folder next to this file.
+
+[buttons]
+# Game controller buttons for a CHIP-8 key by SDL name (a, b, x, y, back, start, leftshoulder,
+# rightshoulder, dpup, dpdown, dpleft, dpright), replacing the default ones. The left stick
+# works like the D-pad. By default:
+# 5 = ["dpup"]
+# 7 = ["dpleft"]
+# 8 = ["dpdown"]
+# 9 = ["dpright"]
+# 6 = ["a"]
+# 4 = ["b"]
+# E = ["x"]
+# D = ["y"]
+"#;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+struct Entry {
+    line: usize,
+    section: String,
+    key: String,
+    value: Value,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+    }
+
+    // Nothing but whitespace or a comment may follow.
+    fn end(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            None | Some('#') => Ok(()),
+            Some(c) => Err(format!("unexpected `{}`", c))
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        if self.peek() == Some('"') {
+            return self.string();
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(String::from("expected a key"));
+        }
+        Ok(self.text[start..self.pos].to_string())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.next();
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                None => return Err(String::from("unterminated string")),
+                Some(c) if Some(c) == quote => return Ok(string),
+                Some('\\') if quote == Some('"') => match self.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some(c @ ('"' | '\\')) => string.push(c),
+                    _ => return Err(String::from("unsupported escape in string"))
+                },
+                Some(c) => string.push(c)
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"' | '\'') => self.string().map(Value::String),
+            Some('[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() == Some(']') {
+                        self.pos += 1;
+                        return Ok(Value::Array(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => (),
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return Err(String::from("expected `,` or `]` in array"))
+                    }
+                }
+            },
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || "+-._".contains(c)) {
+                    self.pos += 1;
+                }
+                let word = self.text[start..self.pos].replace('_', "");
+                match word.as_str() {
+                    "true" => Ok(Value::Boolean(true)),
+                    "false" => Ok(Value::Boolean(false)),
+                    _ => word.parse().map(Value::Integer)
+                        .or_else(|_| word.parse().map(Value::Float))
+                        .map_err(|_| format!("invalid value `{}`", &self.text[start..self.pos]))
+                }
+            }
+        }
+    }
+}
+
+fn parse_toml(text: &str) -> Result<Vec<Entry>, (usize, String)> {
+    let mut entries = Vec::new();
+    let mut section = String::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let mut parser = Parser { text: line, pos: 0 };
+        let error = |message: String| (number + 1, message);
+        parser.skip_whitespace();
+        match parser.peek() {
+            None | Some('#') => continue,
+            Some('[') => {
+                parser.pos += 1;
+                section = parser.key().map_err(error)?;
+                parser.skip_whitespace();
+                if parser.next() != Some(']') {
+                    return Err(error(String::from("expected `]`")));
+                }
+            },
+            _ => {
+                let key = parser.key().map_err(error)?;
+                parser.skip_whitespace();
+                if parser.next() != Some('=') {
+                    return Err(error(String::from("expected `=`")));
+                }
+                let value = parser.value().map_err(error)?;
+                entries.push(Entry { line: number + 1, section: section.clone(), key, value });
+            }
+        }
+        parser.end().map_err(error)?;
+    }
+
+    Ok(entries)
+}
+
+// Folder of the configuration file and the user's ROM database.
+pub fn config_dir() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("chip8"))
+}
+
+fn parse_chip8_key(name: &str) -> Result<usize, String> {
+    usize::from_str_radix(name, 16).ok()
+        .filter(|key| *key < NUM_CHIP8_KEYS)
+        .ok_or(format!("unknown CHIP-8 key {}", name))
+}
+
+// Applies an entry of a [buttons] section: `CHIP8_KEY = [BUTTON_NAMES]`.
+fn apply_buttons_entry(keymap: &mut Keymap, key: &str, value: Value) -> Result<(), String> {
+    let chip8_key = parse_chip8_key(key)?;
+    let Value::Array(buttons) = value else {
+        return Err(format!("invalid value for buttons.{}", key));
+    };
+    let buttons = buttons.iter()
+        .map(|button| match button {
+            Value::String(button) => Button::from_string(button).ok_or(format!("unknown button name `{}`", button)),
+            _ => Err(format!("invalid value for buttons.{}", key))
+        })
+        .collect::<Result<Vec<Button>, String>>()?;
+    keymap.bind_buttons(chip8_key, buttons);
+    Ok(())
+}
+
+// Applies an entry of a [keys] section: `layout = NAME` or `CHIP8_KEY = [HOST_KEY_NAMES]`.
+fn apply_keys_entry(keymap: &mut Keymap, key: &str, value: Value) -> Result<(), String> {
+    match (key, value) {
+        ("layout", Value::String(layout)) => {
+            *keymap = Keymap::from_layout(&layout)
+                .ok_or(format!("unknown layout `{}`, expected one of {}", layout, LAYOUT_NAMES.join(", ")))?;
+        },
+        (chip8_key, Value::Array(host_keys)) => {
+            let chip8_key = parse_chip8_key(chip8_key)?;
+            let host_keys = host_keys.iter()
+                .map(|host_key| match host_key {
+                    Value::String(host_key) => Keycode::from_name(host_key).ok_or(format!("unknown key name `{}`", host_key)),
+                    _ => Err(format!("invalid value for keys.{:X}", chip8_key))
+                })
+                .collect::<Result<Vec<Keycode>, String>>()?;
+            keymap.bind(chip8_key, host_keys);
+        },
+        (key, _) => return Err(format!("invalid value for keys.{}", key))
+    }
+    Ok(())
+}
+
+fn speed_value(speed: f64) -> Option<f64> {
+    Some(speed).filter(|speed| (MIN_SPEED..=MAX_SPEED).contains(speed))
+}
+
+fn apply_entry(options: &mut Options, entry: Entry) -> Result<(), String> {
+    let name = if entry.section.is_empty() { entry.key.clone() } else { format!("{}.{}", entry.section, entry.key) };
+    let invalid = || format!("invalid value for {}", name);
+
+    match (entry.section.as_str(), entry.key.as_str(), entry.value) {
+        ("", "rom_dir", Value::String(dir)) => options.rom_dir = Some(dir).filter(|dir| !dir.is_empty()),
+        ("display", "scale", Value::Integer(scale)) => {
+            options.scale = u32::try_from(scale).ok().filter(|scale| *scale > 0).ok_or_else(invalid)?;
+        },
+        ("display", "palette", Value::String(palette)) => options.palette = parse_palette(&palette).ok_or_else(invalid)?,
+        ("display", "fullscreen", Value::Boolean(fullscreen)) => options.fullscreen = fullscreen,
+        ("speed", "cpu_hz", Value::Integer(hz)) => {
+            options.cpu_hz = u32::try_from(hz).ok().filter(|hz| *hz > 0).ok_or_else(invalid)?;
+        },
+        ("speed", "vip_timing", Value::Boolean(vip_timing)) => options.vip_timing = vip_timing,
+        ("speed", "speed", Value::Integer(speed)) => options.speed = speed_value(speed as f64).ok_or_else(invalid)?,
+        ("speed", "speed", Value::Float(speed)) => options.speed = speed_value(speed).ok_or_else(invalid)?,
+        ("speed", "fast_forward", Value::String(speed)) if speed == "max" => options.fast_forward = None,
+        ("speed", "fast_forward", Value::Integer(speed)) => options.fast_forward = Some(speed_value(speed as f64).ok_or_else(invalid)?),
+        ("speed", "fast_forward", Value::Float(speed)) => options.fast_forward = Some(speed_value(speed).ok_or_else(invalid)?),
+        ("audio", "muted", Value::Boolean(muted)) => options.beep.muted = muted,
+        ("audio", "waveform", Value::String(waveform)) => options.beep.waveform = Waveform::from_name(&waveform).ok_or_else(invalid)?,
+        ("audio", "frequency", Value::Integer(hz)) if hz > 0 => options.beep.frequency = hz as f32,
+        ("audio", "frequency", Value::Float(hz)) if hz > 0.0 => options.beep.frequency = hz as f32,
+        ("audio", "volume", Value::Integer(volume)) => {
+            let volume = u8::try_from(volume).ok().filter(|volume| *volume <= 100).ok_or_else(invalid)?;
+            options.beep.volume = volume as f32 / 100.0;
+        },
+        ("emulation", "quirks", Value::String(preset)) if preset.is_empty() => options.quirks = None,
+        ("emulation", "quirks", Value::String(preset)) => options.quirks = Some(Quirks::from_preset_name(&preset).ok_or_else(invalid)?),
+        ("emulation", "display_wait", Value::Boolean(display_wait)) => options.display_wait = Some(display_wait),
+        ("emulation", "key_wait", Value::String(wait)) if wait == "press" => options.key_wait_on_press = true,
+        ("emulation", "key_wait", Value::String(wait)) if wait == "release" => options.key_wait_on_press = false,
+        ("keys", key, value) => apply_keys_entry(&mut options.keymap, key, value)?,
+        ("buttons", key, value) => apply_buttons_entry(&mut options.keymap, key, value)?,
+        ("", "rom_dir", _)
+            | ("display", "scale" | "palette" | "fullscreen", _)
+            | ("speed", "cpu_hz" | "vip_timing" | "speed" | "fast_forward", _)
+            | ("audio", "muted" | "waveform" | "frequency" | "volume", _)
+            | ("emulation", "quirks" | "display_wait" | "key_wait", _) => {
+            return Err(invalid());
+        },
+        _ => return Err(format!("unknown setting {}", name))
+    }
+    Ok(())
+}
+
+// Default options, with the settings of the configuration file applied.
+// Writes the default configuration file if there is none.
+pub fn load() -> Result<Options, String> {
+    let mut options = Options::default();
+    let Some(path) = config_dir().map(|dir| dir.join("config.toml")) else {
+        return Ok(options);
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) if !path.exists() => {
+            let written = path.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| fs::write(&path, DEFAULT_CONFIG));
+            match written {
+                Ok(()) => println!("Wrote default configuration to {}", path.display()),
+                Err(err) => eprintln!("Unable to write default configuration to {}: {}", path.display(), err)
+            }
+            return Ok(options);
+        },
+        Err(err) => return Err(format!("Unable to read {}: {}", path.display(), err))
+    };
+
+    let entries = parse_toml(&text).map_err(|(line, err)| format!("{}:{}: {}", path.display(), line, err))?;
+    for entry in entries {
+        let line = entry.line;
+        apply_entry(&mut options, entry).map_err(|err| format!("{}:{}: {}", path.display(), line, err))?;
+    }
+    Ok(options)
+}
+
+// Per-ROM keys and buttons, stored as keys/<SHA-1 of the ROM>.toml in the configuration folder.
+fn rom_keys_path(rom_hash: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("keys").join(format!("{}.toml", rom_hash)))
+}
+
+// Applies the ROM's keys and buttons to the keymap, if any were saved. Returns true if there were.
+pub fn load_rom_keys(rom_hash: &str, keymap: &mut Keymap) -> Result<bool, String> {
+    let Some(path) = rom_keys_path(rom_hash).filter(|path| path.exists()) else {
+        return Ok(false);
+    };
+    let text = fs::read_to_string(&path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+    let entries = parse_toml(&text).map_err(|(line, err)| format!("{}:{}: {}", path.display(), line, err))?;
+    for entry in entries {
+        let result = match entry.section.as_str() {
+            "keys" => apply_keys_entry(keymap, &entry.key, entry.value),
+            "buttons" => apply_buttons_entry(keymap, &entry.key, entry.value),
+            _ => Err(format!("unknown setting {}.{}", entry.section, entry.key))
+        };
+        result.map_err(|err| format!("{}:{}: {}", path.display(), entry.line, err))?;
+    }
+    Ok(true)
+}
+
+// Saves the keymap as the ROM's keys and buttons, returning the file it was saved to.
+pub fn save_rom_keys(rom_hash: &str, game_path: &str, keymap: &Keymap) -> Result<PathBuf, String> {
+    let path = rom_keys_path(rom_hash).ok_or("No configuration folder, set HOME or XDG_CONFIG_HOME")?;
+
+    let game_name = Path::new(game_path).file_name().map_or(game_path.into(), |name| name.to_string_lossy());
+    let quote = |name: String| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut text = format!("# Keys for {}, in the format of [keys] and [buttons] in config.toml.\n\n[keys]\n", game_name);
+    for chip8_key in 0..NUM_CHIP8_KEYS {
+        let names = keymap.host_keys(chip8_key).iter().map(|host_key| quote(host_key.name())).collect::<Vec<String>>();
+        text += &format!("{:X} = [{}]\n", chip8_key, names.join(", "));
+    }
+    text += "\n[buttons]\n";
+    for chip8_key in 0..NUM_CHIP8_KEYS {
+        let names = keymap.buttons(chip8_key).iter().map(|button| quote(button.string())).collect::<Vec<String>>();
+        text += &format!("{:X} = [{}]\n", chip8_key, names.join(", "));
+    }
+
+    path.parent().map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&path, text))
+        .map_err(|err| format!("Unable to write {}: {}", path.display(), err))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Options with the settings of `text` applied, or the first error as load reports it.
+    fn options(text: &str) -> Result<Options, String> {
+        let mut options = Options::default();
+        for entry in parse_toml(text).map_err(|(line, err)| format!("{}: {}", line, err))? {
+            let line = entry.line;
+            apply_entry(&mut options, entry).map_err(|err| format!("{}: {}", line, err))?;
+        }
+        Ok(options)
+    }
+
+    #[test]
+    fn default_config_applies_cleanly() {
+        let options = options(DEFAULT_CONFIG).unwrap();
+        assert_eq!(options.cpu_hz, 480);
+        assert_eq!(options.fast_forward, None);
+        assert_eq!(options.display_wait, None);
+        assert!(!options.key_wait_on_press);
+    }
+
+    #[test]
+    fn strings_with_escapes() {
+        let value = |text: &str| parse_toml(&format!("key = {}", text)).unwrap().remove(0).value;
+        assert_eq!(value(r#""a \"b\" \\ c\td\n""#), Value::String(String::from("a \"b\" \\ c\td\n")));
+        assert_eq!(value(r#"'C:\roms\'"#), Value::String(String::from(r"C:\roms\")));
+        assert_eq!(value(r##""# not a comment""##), Value::String(String::from("# not a comment")));
+
+        assert_eq!(parse_toml(r#"key = "\x""#).err(), Some((1, String::from("unsupported escape in string"))));
+        assert_eq!(parse_toml(r#"key = "open"#).err(), Some((1, String::from("unterminated string"))));
+    }
+
+    #[test]
+    fn values_and_comments() {
+        let entries = parse_toml("a = 1_000 # one thousand\n\n# comment\n[s] # section\nb = [1, 2.5, true,] # array\nc = -0.5").unwrap();
+        let values: Vec<_> = entries.iter().map(|entry| (entry.line, entry.section.as_str(), entry.key.as_str(), entry.value.clone())).collect();
+        assert_eq!(values, [
+            (1, "", "a", Value::Integer(1000)),
+            (5, "s", "b", Value::Array(vec![Value::Integer(1), Value::Float(2.5), Value::Boolean(true)])),
+            (6, "s", "c", Value::Float(-0.5))
+        ]);
+
+        assert_eq!(parse_toml("a = 1 2").err(), Some((1, String::from("unexpected `2`"))));
+        assert_eq!(parse_toml("[s\n").err(), Some((1, String::from("expected `]`"))));
+        assert_eq!(parse_toml("ok = 1\na 1").err(), Some((2, String::from("expected `=`"))));
+    }
+
+    #[test]
+    fn settings_are_applied_with_comments_after_values() {
+        let options = options("rom_dir = \"roms\" # where the games are\n[speed]\ncpu_hz = 700 # faster\nspeed = 0.5\n\
+            [emulation]\ndisplay_wait = false\nkey_wait = \"press\"").unwrap();
+        assert_eq!(options.rom_dir.as_deref(), Some("roms"));
+        assert_eq!(options.cpu_hz, 700);
+        assert_eq!(options.speed, 0.5);
+        assert_eq!(options.display_wait, Some(false));
+        assert!(options.key_wait_on_press);
+    }
+
+    #[test]
+    fn unknown_keys_and_sections_are_errors() {
+        assert_eq!(options("[speed]\nwarp = 9").err(), Some(String::from("2: unknown setting speed.warp")));
+        assert_eq!(options("[video]\nscale = 2").err(), Some(String::from("2: unknown setting video.scale")));
+        assert_eq!(options("scale = 2").err(), Some(String::from("1: unknown setting scale")));
+        assert_eq!(options("[speed]\nscale = 2").err(), Some(String::from("2: unknown setting speed.scale")));
+    }
+
+    #[test]
+    fn bad_values_are_errors() {
+        assert_eq!(options("[display]\nfullscreen = yes").err(), Some(String::from("2: invalid value `yes`")));
+        assert_eq!(options("[display]\nfullscreen = 1").err(), Some(String::from("2: invalid value for display.fullscreen")));
+        assert_eq!(options("[audio]\nmuted = \"true\"").err(), Some(String::from("2: invalid value for audio.muted")));
+        assert_eq!(options("[emulation]\nkey_wait = \"hold\"").err(), Some(String::from("2: invalid value for emulation.key_wait")));
+    }
+
+    #[test]
+    fn speeds_must_be_in_range() {
+        assert_eq!(options("[speed]\nspeed = 64").unwrap().speed, MAX_SPEED);
+        assert_eq!(options("[speed]\nfast_forward = 0.125").unwrap().fast_forward, Some(MIN_SPEED));
+        for setting in ["speed = 0", "speed = 65", "speed = -1", "fast_forward = 0.1", "fast_forward = \"fast\"", "cpu_hz = 0"] {
+            let name = setting.split(' ').next().unwrap();
+            assert_eq!(options(&format!("[speed]\n{}", setting)).err(), Some(format!("2: invalid value for speed.{}", name)));
+        }
+    }
+}
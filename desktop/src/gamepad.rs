@@ -0,0 +1,126 @@
+// Game controllers: opened and closed as they are plugged in and out, with the left stick
+// working like the D-pad.
+
+use std::collections::HashMap;
+
+use sdl2::{controller::{Axis, Button, GameController}, event::Event, GameControllerSubsystem, Sdl};
+
+// How far the stick has to be pushed to press a direction, out of 32767. Releasing it
+// below the lower threshold keeps small wobbles around the edge from repeating presses.
+const STICK_PRESS: i16 = 16000;
+const STICK_RELEASE: i16 = 12000;
+
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    // Controllers are closed when dropped, so the open ones are kept here.
+    controllers: Vec<GameController>,
+    sticks: Sticks,
+}
+
+// D-pad buttons the left sticks press on each axis (x, y), by controller instance id.
+#[derive(Default)]
+struct Sticks {
+    pressed: HashMap<u32, [Option<Button>; 2]>,
+}
+
+impl Gamepads {
+    // Controllers already plugged in are opened by the ControllerDeviceAdded events SDL
+    // sends for them at start up.
+    pub fn new(sdl_context: &Sdl) -> Result<Gamepads, String> {
+        Ok(Gamepads { subsystem: sdl_context.game_controller()?, controllers: Vec::new(), sticks: Sticks::default() })
+    }
+
+    // Opens or closes a controller on hotplug events.
+    pub fn handle_hotplug(&mut self, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => match self.subsystem.open(which) {
+                Ok(controller) => {
+                    println!("Connected {}", controller.name());
+                    self.controllers.push(controller);
+                },
+                Err(err) => eprintln!("Unable to open game controller {}: {}", which, err)
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.sticks.remove(which);
+                if let Some(i) = self.controllers.iter().position(|controller| controller.instance_id() == which) {
+                    println!("Disconnected {}", self.controllers.remove(i).name());
+                }
+            },
+            _ => ()
+        }
+    }
+
+    // D-pad buttons pressed (true) or released (false) by moving a controller's left stick.
+    pub fn stick_motion(&mut self, which: u32, axis: Axis, value: i16) -> Vec<(Button, bool)> {
+        self.sticks.motion(which, axis, value)
+    }
+}
+
+impl Sticks {
+    fn motion(&mut self, which: u32, axis: Axis, value: i16) -> Vec<(Button, bool)> {
+        let (index, negative, positive) = match axis {
+            Axis::LeftX => (0, Button::DPadLeft, Button::DPadRight),
+            Axis::LeftY => (1, Button::DPadUp, Button::DPadDown),
+            _ => return Vec::new()
+        };
+
+        let stick = self.pressed.entry(which).or_default();
+        let pressed = match stick[index] {
+            Some(_) if value.unsigned_abs() > STICK_RELEASE as u16 => Some(if value < 0 { negative } else { positive }),
+            None if value.unsigned_abs() > STICK_PRESS as u16 => Some(if value < 0 { negative } else { positive }),
+            _ => None
+        };
+        if pressed == stick[index] {
+            return Vec::new();
+        }
+
+        let mut changes = Vec::new();
+        if let Some(released) = stick[index] {
+            changes.push((released, false));
+        }
+        if let Some(pressed) = pressed {
+            changes.push((pressed, true));
+        }
+        stick[index] = pressed;
+        changes
+    }
+
+    // Forgets a removed controller's stick, without releasing anything.
+    fn remove(&mut self, which: u32) {
+        self.pressed.remove(&which);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_controller_has_its_own_stick() {
+        let mut sticks = Sticks::default();
+        assert_eq!(sticks.motion(0, Axis::LeftX, -20000), [(Button::DPadLeft, true)]);
+        // The second controller's stick at rest releases nothing of the first's.
+        assert_eq!(sticks.motion(1, Axis::LeftX, 0), []);
+        assert_eq!(sticks.motion(1, Axis::LeftX, -20000), [(Button::DPadLeft, true)]);
+        assert_eq!(sticks.motion(0, Axis::LeftX, 0), [(Button::DPadLeft, false)]);
+        assert_eq!(sticks.motion(1, Axis::LeftX, 20000), [(Button::DPadLeft, false), (Button::DPadRight, true)]);
+    }
+
+    #[test]
+    fn stick_is_forgotten_when_its_controller_is_removed() {
+        let mut sticks = Sticks::default();
+        sticks.motion(0, Axis::LeftY, 20000);
+        sticks.remove(0);
+        // A controller given the same instance id presses the direction again.
+        assert_eq!(sticks.motion(0, Axis::LeftY, 20000), [(Button::DPadDown, true)]);
+    }
+
+    #[test]
+    fn stick_releases_below_the_lower_threshold() {
+        let mut sticks = Sticks::default();
+        assert_eq!(sticks.motion(0, Axis::LeftY, -14000), []);
+        assert_eq!(sticks.motion(0, Axis::LeftY, -17000), [(Button::DPadUp, true)]);
+        assert_eq!(sticks.motion(0, Axis::LeftY, -14000), []);
+        assert_eq!(sticks.motion(0, Axis::LeftY, -11000), [(Button::DPadUp, false)]);
+    }
+}
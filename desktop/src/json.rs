@@ -0,0 +1,266 @@
+// Minimal JSON reader, for the ROM database.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    // Members in file order.
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { text, pos: 0 };
+        let value = parser.value().and_then(|value| {
+            parser.skip_whitespace();
+            match parser.peek() {
+                None => Ok(value),
+                Some(_) => Err(String::from("unexpected data after the end"))
+            }
+        });
+        // Errors are reported with the line they were found on.
+        value.map_err(|err| format!("line {}: {}", text[..parser.pos].lines().count().max(1), err))
+    }
+
+    // Member of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Json)]> {
+        match self {
+            Json::Object(members) => Some(members),
+            _ => None
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("expected `{}`, found `{}`", expected, c)),
+            None => Err(format!("expected `{}`, found the end", expected))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.expect('"')?;
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => (),
+                        Some('}') => return Ok(Json::Object(members)),
+                        _ => return Err(String::from("expected `,` or `}` in object"))
+                    }
+                }
+            },
+            Some('[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => (),
+                        Some(']') => return Ok(Json::Array(values)),
+                        _ => return Err(String::from("expected `,` or `]` in array"))
+                    }
+                }
+            },
+            Some('"') => {
+                self.pos += 1;
+                self.string().map(Json::String)
+            },
+            Some(_) => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+                    self.pos += 1;
+                }
+                match &self.text[start..self.pos] {
+                    "null" => Ok(Json::Null),
+                    "true" => Ok(Json::Bool(true)),
+                    "false" => Ok(Json::Bool(false)),
+                    "" => Err(String::from("expected a value")),
+                    word => word.parse().map(Json::Number).map_err(|_| format!("invalid value `{}`", word))
+                }
+            },
+            None => Err(String::from("expected a value, found the end"))
+        }
+    }
+
+    // Rest of a string, after its opening quote.
+    fn string(&mut self) -> Result<String, String> {
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                None => return Err(String::from("unterminated string")),
+                Some('"') => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('u') => {
+                        let mut code = self.hex4()?;
+                        // Characters outside the Basic Multilingual Plane are escaped as a surrogate pair.
+                        if (0xD800..0xDC00).contains(&code) && self.text[self.pos..].starts_with("\\u") {
+                            let after_high = self.pos;
+                            self.pos += 2;
+                            match self.hex4()? {
+                                low @ 0xDC00..0xE000 => code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00),
+                                // Not a low surrogate: the high one is unpaired, and the next escape is read on its own.
+                                _ => self.pos = after_high
+                            }
+                        }
+                        string.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    },
+                    Some(c @ ('"' | '\\' | '/')) => string.push(c),
+                    _ => return Err(String::from("invalid escape in string"))
+                },
+                Some(c) => string.push(c)
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.pos..self.pos + 4).ok_or("invalid \\u escape in string")?;
+        let code = u32::from_str_radix(digits, 16).ok()
+            .filter(|_| digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or("invalid \\u escape in string")?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(text: &str) -> String {
+        Json::parse(text).unwrap().as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(string(r#""a\"b\\c\/d""#), "a\"b\\c/d");
+        assert_eq!(string(r#""\n\t\r\b\f""#), "\n\t\r\u{8}\u{c}");
+        assert_eq!(string(r#""é€""#), "é€");
+        assert_eq!(string(r#""😀""#), "😀");
+        // A surrogate pair, and unpaired surrogates.
+        assert_eq!(string(r#""\ud83d\ude00""#), "😀");
+        assert_eq!(string(r#""\ud83d""#), "\u{FFFD}");
+        assert_eq!(string(r#""\ud83dA""#), "\u{FFFD}A");
+        assert_eq!(string(r#""\ud83d\u0041""#), "\u{FFFD}A");
+        assert_eq!(string(r#""\ud83d\ud83d\ude00""#), "\u{FFFD}😀");
+        assert_eq!(string(r#""\ude00""#), "\u{FFFD}");
+
+        assert_eq!(Json::parse(r#""\x""#), Err(String::from("line 1: invalid escape in string")));
+        assert_eq!(Json::parse(r#""\u12""#), Err(String::from("line 1: invalid \\u escape in string")));
+        assert_eq!(Json::parse(r#""open"#), Err(String::from("line 1: unterminated string")));
+    }
+
+    #[test]
+    fn numbers() {
+        for (text, number) in [("0", 0.0), ("-12", -12.0), ("3.25", 3.25), ("1e3", 1000.0), ("-2.5E-1", -0.25)] {
+            assert_eq!(Json::parse(text), Ok(Json::Number(number)), "{}", text);
+        }
+        assert_eq!(Json::parse("12abc"), Err(String::from("line 1: invalid value `12abc`")));
+        assert_eq!(Json::parse("nul"), Err(String::from("line 1: invalid value `nul`")));
+    }
+
+    #[test]
+    fn nesting() {
+        let json = Json::parse("{\n  \"a\": [1, {\"b\": null}, []],\n  \"c\": {\"d\": true, \"e\": {}}\n}").unwrap();
+        let a = json.get("a").and_then(Json::as_array).unwrap();
+        assert_eq!(a[0], Json::Number(1.0));
+        assert_eq!(a[1].get("b"), Some(&Json::Null));
+        assert_eq!(a[2], Json::Array(Vec::new()));
+        assert_eq!(json.get("c").and_then(|c| c.get("d")).and_then(Json::as_bool), Some(true));
+        assert_eq!(json.get("c").and_then(|c| c.get("e")), Some(&Json::Object(Vec::new())));
+        assert_eq!(json.get("missing"), None);
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        assert_eq!(Json::parse("[1,\n2\n3]"), Err(String::from("line 3: expected `,` or `]` in array")));
+        assert_eq!(Json::parse("{\"a\" 1}"), Err(String::from("line 1: expected `:`, found `1`")));
+        assert_eq!(Json::parse("[] []"), Err(String::from("line 1: unexpected data after the end")));
+        assert_eq!(Json::parse(""), Err(String::from("line 1: expected a value, found the end")));
+    }
+}
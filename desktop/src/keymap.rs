@@ -0,0 +1,278 @@
+// Keymap: which host keys and game controller buttons press which CHIP-8 keys.
+
+use sdl2::{controller::Button, keyboard::Keycode};
+
+pub const NUM_CHIP8_KEYS: usize = 16;
+
+// CHIP-8 keys in the order of the COSMAC VIP keypad, row by row:
+//   1 2 3 C
+//   4 5 6 D
+//   7 8 9 E
+//   A 0 B F
+pub const KEYPAD_ORDER: [usize; NUM_CHIP8_KEYS] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF];
+
+// Names accepted by Keymap::from_layout.
+pub const LAYOUT_NAMES: [&str; 5] = ["qwerty", "azerty", "qwertz", "dvorak", "colemak"];
+
+// Host keys main_loop takes as hotkeys before the keymap sees them, so they can't be bound.
+pub const HOTKEYS: [Keycode; 18] = [
+    Keycode::Escape, Keycode::F1, Keycode::F2, Keycode::F3, Keycode::F4, Keycode::F5, Keycode::F6,
+    Keycode::F7, Keycode::F8, Keycode::F9, Keycode::F10, Keycode::F11, Keycode::F12,
+    Keycode::Tab, Keycode::Backspace, Keycode::Minus, Keycode::Equals, Keycode::Backslash
+];
+
+// Controller buttons: the D-pad on the keys most games move with (5, 7, 8, 9, the W A S D of
+// the default keymap) and the face buttons on the keys around them (6, 4, E, D: E, Q, F, R).
+const DEFAULT_BUTTONS: [(usize, Button); 8] = [
+    (0x5, Button::DPadUp), (0x7, Button::DPadLeft), (0x8, Button::DPadDown), (0x9, Button::DPadRight),
+    (0x6, Button::A), (0x4, Button::B), (0xE, Button::X), (0xD, Button::Y)
+];
+
+// Host input holding a CHIP-8 key down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSource {
+    Key(Keycode),
+    // Button of the controller with the instance id.
+    Button(u32, Button),
+    // D-pad button the left stick of the controller with the instance id presses.
+    Stick(u32, Button),
+}
+
+impl InputSource {
+    fn controller(&self) -> Option<u32> {
+        match *self {
+            InputSource::Key(_) => None,
+            InputSource::Button(which, _) | InputSource::Stick(which, _) => Some(which)
+        }
+    }
+}
+
+// Host inputs holding down each CHIP-8 key. With several host keys or buttons bound to a
+// CHIP-8 key, it stays down until the last of them is released.
+#[derive(Debug, Default)]
+pub struct HeldKeys {
+    // Indexed by CHIP-8 key.
+    sources: [Vec<InputSource>; NUM_CHIP8_KEYS],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    // Host keys bound to each CHIP-8 key, indexed by CHIP-8 key.
+    bindings: [Vec<Keycode>; NUM_CHIP8_KEYS],
+    // Controller buttons bound to each CHIP-8 key, indexed by CHIP-8 key.
+    buttons: [Vec<Button>; NUM_CHIP8_KEYS],
+}
+
+// Replaces the bindings of a CHIP-8 key. A host key or button can only press one CHIP-8 key,
+// so it is unbound from any other.
+fn bind<T: PartialEq>(bindings: &mut [Vec<T>; NUM_CHIP8_KEYS], chip8_key: usize, bound: Vec<T>) {
+    for other in bindings.iter_mut() {
+        other.retain(|binding| !bound.contains(binding));
+    }
+    bindings[chip8_key] = bound;
+}
+
+// Adds a binding to a CHIP-8 key, unbinding it from any other.
+fn add<T: PartialEq>(bindings: &mut [Vec<T>; NUM_CHIP8_KEYS], chip8_key: usize, binding: T) {
+    for other in bindings.iter_mut() {
+        other.retain(|bound| *bound != binding);
+    }
+    bindings[chip8_key].push(binding);
+}
+
+impl Default for Keymap {
+    // The COSMAC VIP keypad on the left of a QWERTY keyboard.
+    fn default() -> Self {
+        Keymap::from_keypad_keys([
+            Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4,
+            Keycode::Q, Keycode::W, Keycode::E, Keycode::R,
+            Keycode::A, Keycode::S, Keycode::D, Keycode::F,
+            Keycode::Z, Keycode::X, Keycode::C, Keycode::V
+        ])
+    }
+}
+
+impl HeldKeys {
+    // Returns true if this presses the CHIP-8 key, i.e. nothing held it down before.
+    pub fn press(&mut self, chip8_key: usize, source: InputSource) -> bool {
+        let sources = &mut self.sources[chip8_key];
+        let pressed = sources.is_empty();
+        if !sources.contains(&source) {
+            sources.push(source);
+        }
+        pressed
+    }
+
+    // Returns true if this releases the CHIP-8 key, i.e. it was the last input holding it down.
+    pub fn release(&mut self, chip8_key: usize, source: InputSource) -> bool {
+        let sources = &mut self.sources[chip8_key];
+        let held = !sources.is_empty();
+        sources.retain(|held| *held != source);
+        held && sources.is_empty()
+    }
+
+    // Releases the buttons of a controller that was unplugged. Returns the CHIP-8 keys released.
+    pub fn release_controller(&mut self, which: u32) -> Vec<usize> {
+        let mut released = Vec::new();
+        for (chip8_key, sources) in self.sources.iter_mut().enumerate() {
+            let held = !sources.is_empty();
+            sources.retain(|source| source.controller() != Some(which));
+            if held && sources.is_empty() {
+                released.push(chip8_key);
+            }
+        }
+        released
+    }
+
+    // Forgets every held input, e.g. when the keys are released for rebinding.
+    pub fn clear(&mut self) {
+        self.sources = Default::default();
+    }
+}
+
+impl Keymap {
+    // One host key for each CHIP-8 key, in KEYPAD_ORDER, and the default buttons.
+    fn from_keypad_keys(host_keys: [Keycode; NUM_CHIP8_KEYS]) -> Keymap {
+        let mut keymap = Keymap { bindings: Default::default(), buttons: Default::default() };
+        for (chip8_key, host_key) in KEYPAD_ORDER.into_iter().zip(host_keys) {
+            keymap.bindings[chip8_key].push(host_key);
+        }
+        for (chip8_key, button) in DEFAULT_BUTTONS {
+            keymap.buttons[chip8_key].push(button);
+        }
+        keymap
+    }
+
+    // The keypad on the keys at the left of the keyboard, wherever the layout puts their letters.
+    pub fn from_layout(name: &str) -> Option<Keymap> {
+        let row = [Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4];
+        let keys = |rest: [Keycode; 12]| {
+            let mut host_keys = [Keycode::Num1; NUM_CHIP8_KEYS];
+            host_keys[..4].copy_from_slice(&row);
+            host_keys[4..].copy_from_slice(&rest);
+            Keymap::from_keypad_keys(host_keys)
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "qwerty" => Some(Keymap::default()),
+            "azerty" => Some(keys([
+                Keycode::A, Keycode::Z, Keycode::E, Keycode::R,
+                Keycode::Q, Keycode::S, Keycode::D, Keycode::F,
+                Keycode::W, Keycode::X, Keycode::C, Keycode::V
+            ])),
+            "qwertz" => Some(keys([
+                Keycode::Q, Keycode::W, Keycode::E, Keycode::R,
+                Keycode::A, Keycode::S, Keycode::D, Keycode::F,
+                Keycode::Y, Keycode::X, Keycode::C, Keycode::V
+            ])),
+            "dvorak" => Some(keys([
+                Keycode::Quote, Keycode::Comma, Keycode::Period, Keycode::P,
+                Keycode::A, Keycode::O, Keycode::E, Keycode::U,
+                Keycode::Semicolon, Keycode::Q, Keycode::J, Keycode::K
+            ])),
+            "colemak" => Some(keys([
+                Keycode::Q, Keycode::W, Keycode::F, Keycode::P,
+                Keycode::A, Keycode::R, Keycode::S, Keycode::T,
+                Keycode::Z, Keycode::X, Keycode::C, Keycode::D
+            ])),
+            _ => None
+        }
+    }
+
+    // CHIP-8 key pressed by a host key.
+    pub fn translate(&self, host_key: Keycode) -> Option<usize> {
+        self.bindings.iter().position(|host_keys| host_keys.contains(&host_key))
+    }
+
+    pub fn host_keys(&self, chip8_key: usize) -> &[Keycode] {
+        &self.bindings[chip8_key]
+    }
+
+    // CHIP-8 key pressed by a controller button.
+    pub fn translate_button(&self, button: Button) -> Option<usize> {
+        self.buttons.iter().position(|buttons| buttons.contains(&button))
+    }
+
+    pub fn buttons(&self, chip8_key: usize) -> &[Button] {
+        &self.buttons[chip8_key]
+    }
+
+    // Replaces the host keys of a CHIP-8 key, unbinding them from any other.
+    pub fn bind(&mut self, chip8_key: usize, host_keys: Vec<Keycode>) {
+        bind(&mut self.bindings, chip8_key, host_keys);
+    }
+
+    // Replaces the buttons of a CHIP-8 key, unbinding them from any other.
+    pub fn bind_buttons(&mut self, chip8_key: usize, buttons: Vec<Button>) {
+        bind(&mut self.buttons, chip8_key, buttons);
+    }
+
+    // Adds a host key to a CHIP-8 key, unbinding it from any other.
+    pub fn add(&mut self, chip8_key: usize, host_key: Keycode) {
+        add(&mut self.bindings, chip8_key, host_key);
+    }
+
+    // Adds a button to a CHIP-8 key, unbinding it from any other.
+    pub fn add_button(&mut self, chip8_key: usize, button: Button) {
+        add(&mut self.buttons, chip8_key, button);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_is_released_with_the_last_input_holding_it() {
+        let mut held = HeldKeys::default();
+        assert!(held.press(5, InputSource::Key(Keycode::W)));
+        assert!(!held.press(5, InputSource::Key(Keycode::Up)));
+        // Key repeats don't hold it down twice.
+        assert!(!held.press(5, InputSource::Key(Keycode::W)));
+
+        assert!(!held.release(5, InputSource::Key(Keycode::W)));
+        assert!(held.release(5, InputSource::Key(Keycode::Up)));
+        assert!(!held.release(5, InputSource::Key(Keycode::Up)));
+    }
+
+    #[test]
+    fn inputs_released_while_not_held_are_ignored() {
+        let mut held = HeldKeys::default();
+        held.press(5, InputSource::Key(Keycode::W));
+        held.clear();
+        assert!(!held.release(5, InputSource::Key(Keycode::W)));
+        assert!(held.press(5, InputSource::Key(Keycode::W)));
+    }
+
+    #[test]
+    fn buttons_and_stick_hold_keys_per_controller() {
+        let mut held = HeldKeys::default();
+        assert!(held.press(5, InputSource::Button(0, Button::DPadUp)));
+        assert!(!held.press(5, InputSource::Stick(0, Button::DPadUp)));
+        assert!(!held.press(5, InputSource::Button(1, Button::DPadUp)));
+        assert!(!held.release(5, InputSource::Stick(0, Button::DPadUp)));
+        assert!(!held.release(5, InputSource::Button(1, Button::DPadUp)));
+        assert!(held.release(5, InputSource::Button(0, Button::DPadUp)));
+    }
+
+    #[test]
+    fn unplugging_a_controller_releases_only_its_keys() {
+        let mut held = HeldKeys::default();
+        held.press(5, InputSource::Button(0, Button::DPadUp));
+        held.press(6, InputSource::Button(0, Button::A));
+        held.press(6, InputSource::Key(Keycode::E));
+        held.press(7, InputSource::Stick(1, Button::DPadLeft));
+        assert_eq!(held.release_controller(0), [5]);
+        assert!(held.release(6, InputSource::Key(Keycode::E)));
+        assert!(held.release(7, InputSource::Stick(1, Button::DPadLeft)));
+    }
+
+    #[test]
+    fn layouts_leave_the_hotkeys_free() {
+        for name in LAYOUT_NAMES {
+            let keymap = Keymap::from_layout(name).unwrap();
+            for chip8_key in 0..NUM_CHIP8_KEYS {
+                assert!(keymap.host_keys(chip8_key).iter().all(|host_key| !HOTKEYS.contains(host_key)), "{}", name);
+            }
+        }
+    }
+}
@@ -1,161 +1,281 @@
+mod audio;
+mod cli;
+mod config;
+mod gamepad;
+mod json;
+mod keymap;
+mod rebind;
+mod romdb;
+mod sha1;
+
+use audio::Beeper;
+use gamepad::Gamepads;
+use keymap::{HeldKeys, InputSource};
+use rebind::Rebinding;
 use chip8_core::*;
+use cli::{Command, Options, USAGE};
+use romdb::RomDatabase;
+use sha1::sha1_hex;
 
-use std::{env, fs, io, io::Read, path::PathBuf};
+use std::{env, fs, io, path::Path, path::PathBuf, process, thread, time::Duration, time::Instant};
 use sdl2::{event::Event, EventPump, keyboard::Keycode, pixels::Color, rect::Rect, render::Canvas, render::WindowCanvas, Sdl, video::Window};
 
-const SCALE: u32 = 15;
-const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
-const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
-const TICKS_PER_FRAME: usize = 8;
-const DEFAULT_GAMES_FOLDER_PATH: &str = "/home/jordan/RustProjs/chip8/games/roms/";
+const NUM_SAVE_SLOTS: usize = 10;
+// Time spent running frames per loop when fast-forwarding as fast as possible, leaving the
+// rest of a 60 Hz display refresh to draw and handle events.
+const UNCAPPED_RUN_TIME: Duration = Duration::from_millis(10);
+const REWIND_FRAMES: usize = 10 * 60;  // 10 seconds at 60 fps
 
-fn draw_screen(emulator: &mut Emulator, canvas: &mut Canvas<Window>) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
-
-    let screen_buf = emulator.get_display();
+// Exit codes.
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
 
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
+fn draw_screen(emulator: &mut Emulator, canvas: &mut Canvas<Window>, palette: &[Color; 4]) {
+    canvas.set_draw_color(palette[0]);
+    canvas.clear();
 
-    for (i, pixel) in screen_buf.iter().enumerate() {
-        if *pixel {
-            let x = (i % SCREEN_WIDTH) as u32;
-            let y = (i / SCREEN_WIDTH) as u32;
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
-            canvas.fill_rect(rect).unwrap();
+    let display = emulator.get_display();
+    let width = display.width as u32;
+    let height = display.height as u32;
+
+    // Hi-res pixels are scaled to fill the same area, which keeps the 2:1 aspect ratio
+    // and is centered in windows (or fullscreen displays) of other shapes.
+    let (output_width, output_height) = canvas.output_size().unwrap_or((width, height));
+    let area_width = output_width.min(output_height * 2);
+    let area_height = area_width / 2;
+    let area_left = (output_width - area_width) / 2;
+    let area_top = (output_height - area_height) / 2;
+
+    for (i, pixel) in display.pixels.iter().enumerate() {
+        if *pixel != 0 {
+            let x = (i % display.width) as u32;
+            let y = (i / display.width) as u32;
+            let left = x * area_width / width;
+            let top = y * area_height / height;
+            let right = (x + 1) * area_width / width;
+            let bottom = (y + 1) * area_height / height;
+            let rect = Rect::new((area_left + left) as i32, (area_top + top) as i32, right - left, bottom - top);
+            canvas.set_draw_color(palette[*pixel as usize & 0b11]);
+            // A failed rectangle only leaves a pixel out of this frame.
+            let _ = canvas.fill_rect(rect);
         }
     }
     canvas.present();
 }
 
-fn translate_key_input(key: Keycode) -> Option<usize> {
-    match key {
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Num4 => Some(0xC),
-
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-
-        _ => None
+fn setup_canvas(sdl_context: &Sdl, game_path: &str, options: &Options) -> Result<WindowCanvas, String> {
+    let video_subsystem = sdl_context.video()?;
+    let mut window = video_subsystem.window(game_path, SCREEN_WIDTH as u32 * options.scale, SCREEN_HEIGHT as u32 * options.scale);
+    window.position_centered().opengl();
+    if options.fullscreen {
+        window.fullscreen_desktop();
     }
-}
-
-fn setup_canvas(sdl_context: &Sdl, game_path: &str) -> WindowCanvas {
-    let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window(game_path, WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .opengl()
-        .build()
-        .unwrap();
 
     let mut canvas = window
+        .build()
+        .map_err(|err| err.to_string())?
         .into_canvas()
         .present_vsync()
         .build()
-        .unwrap();
+        .map_err(|err| err.to_string())?;
 
     canvas.clear();
     canvas.present();
-    canvas
+    Ok(canvas)
 }
 
 // Uses index 0 to hold parent dir.
-fn get_folder_contents(folder_path: &str) -> Vec<PathBuf> {
-    let read_contents = fs::read_dir(folder_path).unwrap();
-    let dir_entries: Vec<fs::DirEntry> = read_contents
-        .map(|result| result.unwrap())
-        .collect();
-    let mut path_bufs: Vec<PathBuf> = dir_entries.iter()
-        .map(|entry| entry.path())
-        .collect();
+fn get_folder_contents(folder_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut path_bufs = fs::read_dir(folder_path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
 
     path_bufs.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
 
-    let curr_path_buf = path_bufs[0].parent().unwrap().to_path_buf();
-    let parent_path_buf = curr_path_buf.parent().unwrap().to_path_buf();
+    let parent_path_buf = folder_path.parent().unwrap_or(folder_path).to_path_buf();
     path_bufs.insert(0, parent_path_buf);
 
-    path_bufs
+    Ok(path_bufs)
 }
 
 fn display_folder_contents(contents: &[PathBuf]) {
     println!();
 
     for (i, path) in contents.iter().enumerate() {
-        let filename = path.file_name().unwrap();
+        let filename = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy();
         println!("{}: {}{}{}",
             i,
             if i == 0 { "↑ " } else { "" },
-            filename.to_str().unwrap(),
+            filename,
             if path.is_dir() { "/" } else { "" });
     }
 }
 
-fn get_user_input(max_allowed_number: usize) -> usize {
+// Asks until a number up to max_allowed_number is entered. Fails if stdin is closed.
+fn get_user_input(max_allowed_number: usize) -> io::Result<usize> {
     let mut buffer = String::new();
-    let mut x: i32 = -1;
 
-    while x < 0 || x > max_allowed_number as i32 {
+    loop {
         buffer.clear();
-        io::stdin().read_line(&mut buffer).unwrap();
-        x = buffer.trim().parse::<i32>().expect("Parsed input was not an integer.");
+        if io::stdin().read_line(&mut buffer)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no ROM selected"));
+        }
+        match buffer.trim().parse::<usize>() {
+            Ok(x) if x <= max_allowed_number => return Ok(x),
+            _ => println!("Enter a number from 0 to {}", max_allowed_number)
+        }
     }
-
-    x as usize
 }
 
-fn select_game(games_folder_path: &str) -> String {
-    let mut game_full_path: String = String::new();
+fn select_game(games_folder_path: &Path) -> io::Result<PathBuf> {
+    let mut curr_path = games_folder_path.to_path_buf();
 
-    let mut files: Vec<PathBuf>;
-    let mut selection_number: usize;
-    let mut curr_path: &str = games_folder_path;
-    let mut sub_path: PathBuf;
-
-    while game_full_path.is_empty() {
-        files = get_folder_contents(curr_path);
+    loop {
+        let files = get_folder_contents(&curr_path)?;
         display_folder_contents(&files);
-        selection_number = get_user_input(files.len());
-        sub_path = files[selection_number].clone();
-        curr_path = sub_path.to_str().unwrap();
-        if !sub_path.is_dir() {
-            game_full_path.push_str(curr_path);
+        let selection_number = get_user_input(files.len() - 1)?;
+        curr_path = files[selection_number].clone();
+        if !curr_path.is_dir() {
+            return Ok(curr_path);
         }
     }
+}
 
-    game_full_path
+fn load_game(game_path: &str, rom: &[u8], options: &Options) -> Result<Emulator, String> {
+    let mut chip8 = load_rom(game_path, rom, &options.rom_settings())
+        .map_err(|err| format!("Unable to load {}: {}", game_path, err))?;
+
+    for addr in &options.breakpoints {
+        chip8.add_breakpoint(*addr);
+    }
+    for watchpoint in &options.watchpoints {
+        chip8.add_watchpoint(watchpoint.clone());
+    }
+    Ok(chip8)
 }
 
-fn load_game(game_path: &str) -> Emulator {
-    let mut chip8 = Emulator::new();
+// Save states are stored next to the ROM, one file per slot, e.g. `bug_game.ch8.state0`.
+fn save_state_path(game_path: &str, slot: usize) -> String {
+    format!("{}.state{}", game_path, slot)
+}
 
-    let mut msg = String::from("Unable to open file. Provided path was ");
-    msg.push_str(game_path);
+fn quick_save(chip8: &Emulator, game_path: &str, slot: usize) {
+    let path = save_state_path(game_path, slot);
+    match fs::write(&path, chip8.save_state()) {
+        Ok(()) => println!("Saved state to slot {} ({})", slot, path),
+        Err(err) => eprintln!("Unable to save state to {}: {}", path, err)
+    }
+}
 
-    let mut rom = fs::File::open(game_path).expect(&*msg);
-    let mut buffer = Vec::new();
+fn quick_load(chip8: &mut Emulator, game_path: &str, slot: usize) {
+    let path = save_state_path(game_path, slot);
+    let result = fs::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|data| chip8.load_state(&data).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => println!("Loaded state from slot {} ({})", slot, path),
+        Err(err) => eprintln!("Unable to load state from {}: {}", path, err)
+    }
+}
 
-    rom.read_to_end(&mut buffer).unwrap();
-    chip8.load_data(&buffer);
-    chip8
+// Movies are stored next to the ROM, e.g. `bug_game.ch8.c8m`.
+fn movie_path(game_path: &str) -> String {
+    format!("{}.c8m", game_path)
 }
 
-fn main_loop(chip8: &mut Emulator, event_pump: &mut EventPump, canvas: &mut WindowCanvas) {
+fn toggle_recording(chip8: &mut Emulator, scheduler: &mut Scheduler, game_path: &str) {
+    let Some(movie) = chip8.stop_recording() else {
+        chip8.start_recording(scheduler);
+        println!("Recording movie");
+        return;
+    };
+
+    let path = movie_path(game_path);
+    match fs::write(&path, movie.to_bytes()) {
+        Ok(()) => println!("Saved movie of {} frames to {}", movie.frames(), path),
+        Err(err) => eprintln!("Unable to save movie to {}: {}", path, err)
+    }
+}
+
+fn start_playback(chip8: &mut Emulator, movie_path: &str) -> Result<MoviePlayer, String> {
+    let data = fs::read(movie_path).map_err(|err| err.to_string())?;
+    let movie = Movie::from_bytes(&data).map_err(|err| err.to_string())?;
+    MoviePlayer::start(movie, chip8).map_err(|err| err.to_string())
+}
+
+fn format_watch_hit(hit: &WatchHit) -> String {
+    match hit.access {
+        MemoryAccess::Read => format!("read {:#05X} = {:02X} by {:04X} at PC {:#05X}",
+            hit.addr, hit.new_value, hit.opcode, hit.pc),
+        MemoryAccess::Write => format!("write {:#05X}: {:02X} -> {:02X} by {:04X} at PC {:#05X}",
+            hit.addr, hit.old_value, hit.new_value, hit.opcode, hit.pc)
+    }
+}
+
+fn print_debug_state(chip8: &Emulator) {
+    match chip8.stop_reason() {
+        Some(StopReason::Breakpoint { addr }) => println!("\nBreakpoint at {:#05X}", addr),
+        Some(StopReason::Watchpoint(hit)) => println!("\nWatchpoint: {}", format_watch_hit(&hit)),
+        Some(StopReason::StepComplete) => println!("\nStepped"),
+        Some(StopReason::Blocked) if chip8.is_halted() => println!("\nNothing to step, the program has exited"),
+        Some(StopReason::Blocked) => println!("\nNothing to step until the vertical blank, advance a frame with \\"),
+        Some(StopReason::Requested) | None => println!("\nPaused")
+    }
+    print!("{}", format_registers(chip8));
+}
+
+// The ROM file being run.
+struct RomFile {
+    path: String,
+    // SHA-1 of the ROM, see romdb.
+    hash: String,
+}
+
+// The window and devices of the frontend.
+struct Frontend {
+    canvas: WindowCanvas,
+    event_pump: EventPump,
+    beeper: Option<Beeper>,
+    gamepads: Option<Gamepads>,
+}
+
+// Runs the game until the window is closed or Escape is pressed. Hotkeys:
+// F1 pauses/resumes, `\` advances one frame and pauses again.
+// Holding Tab fast-forwards, - and = halve and double the speed.
+// F5 saves and F9 loads the current save slot, F6/F7 select the previous/next slot.
+// Holding Backspace rewinds, one frame per frame.
+// Debugger: F2 toggles a breakpoint at PC, F10 steps over, F11 steps into,
+// F12 runs until the current subroutine returns. The VM state is printed whenever it stops.
+// F3 starts/stops recording a movie of the keypad input. F4 toggles mute.
+// F8 shows the screen to rebind the keys, and saves them for the ROM.
+// While a movie is recorded or played back, rewinding and loading states are disabled,
+// and during playback the keypad only takes input from the movie.
+fn main_loop(chip8: &mut Emulator, rom_file: &RomFile, options: &Options, frontend: &mut Frontend,
+             player: &mut Option<MoviePlayer>) -> Result<(), Chip8Error> {
+    let Frontend { canvas, event_pump, beeper, gamepads } = frontend;
+    let game_path = rom_file.path.as_str();
+    let mut keymap = options.keymap.clone();
+    let mut held_keys = HeldKeys::default();
+    let mut rebinding = false;
+    // A movie is played back with the timing and speed it was recorded with.
+    let mut scheduler = match player {
+        Some(movie_player) => movie_player.movie().scheduler(),
+        None => Scheduler::with_timing(options.timing())
+    };
+    let mut last_time = Instant::now();
+    let mut save_slot: usize = 0;
+    let mut rewind_buffer = RewindBuffer::new(REWIND_FRAMES);
+    let mut rewinding = false;
+    let mut stop_printed = false;
+    let mut speed = player.as_ref().map_or(options.speed, |movie_player| movie_player.movie().speed());
+    let mut fast_forward = false;
+    let mut advance_frame = false;
+    let base_title = canvas.window().title().to_string();
+    let mut speed_label = String::new();
+    if options.start_paused {
+        chip8.pause();
+    }
+
     'gameloop: loop {
         for event in event_pump.poll_iter() {
             match event {
@@ -163,49 +283,377 @@ fn main_loop(chip8: &mut Emulator, event_pump: &mut EventPump, canvas: &mut Wind
                     | Event::KeyDown{keycode: Some(Keycode::Escape), ..} => {
                     break 'gameloop;
                 },
-                Event::KeyDown{keycode: Some(key), ..} => {
-                    if let Some(chip8_input) = translate_key_input(key) {
-                        chip8.keypress(chip8_input, true);
+                Event::KeyDown{keycode: Some(Keycode::F1), repeat: false, ..} => {
+                    if chip8.is_paused() {
+                        chip8.resume();
+                    } else {
+                        chip8.pause();
+                    }
+                    stop_printed = false;
+                },
+                Event::KeyDown{keycode: Some(Keycode::F2), repeat: false, ..} => {
+                    let pc = chip8.registers().pc;
+                    if chip8.remove_breakpoint(pc) {
+                        println!("Removed breakpoint at {:#05X}", pc);
+                    } else {
+                        chip8.add_breakpoint(pc);
+                        println!("Added breakpoint at {:#05X}", pc);
+                    }
+                },
+                Event::KeyDown{keycode: Some(Keycode::Backslash), ..} => {
+                    advance_frame = true;
+                },
+                Event::KeyDown{keycode: Some(Keycode::Tab), repeat: false, ..} => {
+                    fast_forward = true;
+                },
+                Event::KeyUp{keycode: Some(Keycode::Tab), ..} => {
+                    fast_forward = false;
+                },
+                Event::KeyDown{keycode: Some(Keycode::Minus), repeat: false, ..} => {
+                    speed = (speed / 2.0).max(MIN_SPEED);
+                },
+                Event::KeyDown{keycode: Some(Keycode::Equals), repeat: false, ..} => {
+                    speed = (speed * 2.0).min(MAX_SPEED);
+                },
+                Event::KeyDown{keycode: Some(Keycode::F10), ..} => {
+                    chip8.step_over()?;
+                    stop_printed = false;
+                },
+                Event::KeyDown{keycode: Some(Keycode::F11), ..} => {
+                    chip8.step()?;
+                    stop_printed = false;
+                },
+                Event::KeyDown{keycode: Some(Keycode::F12), repeat: false, ..} => {
+                    chip8.run_to_return();
+                    stop_printed = false;
+                },
+                Event::KeyDown{keycode: Some(Keycode::Backspace), ..} => {
+                    rewinding = !chip8.is_recording() && player.is_none();
+                },
+                Event::KeyUp{keycode: Some(Keycode::Backspace), ..} => {
+                    rewinding = false;
+                },
+                Event::KeyDown{keycode: Some(Keycode::F4), repeat: false, ..} => {
+                    if let Some(beeper) = beeper {
+                        println!("{}", if beeper.toggle_mute() { "Muted" } else { "Unmuted" });
                     }
                 },
-                Event::KeyUp{keycode: Some(key), ..} => {
-                    if let Some(chip8_input) = translate_key_input(key) {
-                        chip8.keypress(chip8_input, false);
+                Event::KeyDown{keycode: Some(Keycode::F5), repeat: false, ..} => {
+                    quick_save(chip8, game_path, save_slot);
+                },
+                Event::KeyDown{keycode: Some(Keycode::F9), repeat: false, ..} => {
+                    if chip8.is_recording() || player.is_some() {
+                        println!("Loading states is disabled while a movie is recorded or played");
+                    } else {
+                        quick_load(chip8, game_path, save_slot);
+                    }
+                },
+                Event::KeyDown{keycode: Some(Keycode::F3), repeat: false, ..} if player.is_none() => {
+                    toggle_recording(chip8, &mut scheduler, game_path);
+                },
+                Event::KeyDown{keycode: Some(Keycode::F8), repeat: false, ..} => {
+                    rebinding = true;
+                },
+                Event::KeyDown{keycode: Some(Keycode::F6), repeat: false, ..} => {
+                    save_slot = (save_slot + NUM_SAVE_SLOTS - 1) % NUM_SAVE_SLOTS;
+                    println!("Save slot {}", save_slot);
+                },
+                Event::KeyDown{keycode: Some(Keycode::F7), repeat: false, ..} => {
+                    save_slot = (save_slot + 1) % NUM_SAVE_SLOTS;
+                    println!("Save slot {}", save_slot);
+                },
+                Event::KeyDown{keycode: Some(key), ..} if player.is_none() => {
+                    if let Some(chip8_input) = keymap.translate(key) {
+                        if held_keys.press(chip8_input, InputSource::Key(key)) {
+                            chip8.keypress(chip8_input, true)?;
+                        }
+                    }
+                },
+                Event::KeyUp{keycode: Some(key), ..} if player.is_none() => {
+                    if let Some(chip8_input) = keymap.translate(key) {
+                        if held_keys.release(chip8_input, InputSource::Key(key)) {
+                            chip8.keypress(chip8_input, false)?;
+                        }
+                    }
+                },
+                Event::ControllerDeviceAdded {..} | Event::ControllerDeviceRemoved {..} => {
+                    if let Some(gamepads) = gamepads {
+                        gamepads.handle_hotplug(&event);
+                    }
+                    if let Event::ControllerDeviceRemoved { which, .. } = event {
+                        for chip8_input in held_keys.release_controller(which) {
+                            chip8.keypress(chip8_input, false)?;
+                        }
+                    }
+                },
+                Event::ControllerButtonDown{which, button, ..} if player.is_none() => {
+                    if let Some(chip8_input) = keymap.translate_button(button) {
+                        if held_keys.press(chip8_input, InputSource::Button(which, button)) {
+                            chip8.keypress(chip8_input, true)?;
+                        }
+                    }
+                },
+                Event::ControllerButtonUp{which, button, ..} if player.is_none() => {
+                    if let Some(chip8_input) = keymap.translate_button(button) {
+                        if held_keys.release(chip8_input, InputSource::Button(which, button)) {
+                            chip8.keypress(chip8_input, false)?;
+                        }
+                    }
+                },
+                Event::ControllerAxisMotion{which, axis, value, ..} if player.is_none() => {
+                    let changes = gamepads.as_mut().map_or(Vec::new(), |gamepads| gamepads.stick_motion(which, axis, value));
+                    for (button, pressed) in changes {
+                        let Some(chip8_input) = keymap.translate_button(button) else {
+                            continue;
+                        };
+                        let source = InputSource::Stick(which, button);
+                        let changed = if pressed { held_keys.press(chip8_input, source) } else { held_keys.release(chip8_input, source) };
+                        if changed {
+                            chip8.keypress(chip8_input, pressed)?;
+                        }
                     }
                 }
                 _ => ()
             }
         }
 
-        for _ in 0..TICKS_PER_FRAME {
-            chip8.tick();
+        if rebinding {
+            rebinding = false;
+            if let Some(beeper) = beeper {
+                beeper.set_active(false);
+            }
+            // The keys are released, as the rebinding screen takes their key ups.
+            held_keys.clear();
+            for key in 0..keymap::NUM_CHIP8_KEYS {
+                if chip8.is_key_pressed(key) {
+                    chip8.keypress(key, false)?;
+                }
+            }
+            match rebind::rebind_keys(event_pump, canvas, gamepads, &mut keymap, &options.palette) {
+                Rebinding::Done => {
+                    match config::save_rom_keys(&rom_file.hash, game_path, &keymap) {
+                        Ok(path) => println!("Saved keys to {}", path.display()),
+                        Err(err) => eprintln!("{}", err)
+                    }
+                },
+                Rebinding::Cancelled => (),
+                Rebinding::Quit => break 'gameloop
+            }
+            // The game stood still while the rebinding screen was shown.
+            last_time = Instant::now();
+        }
+
+        // Timers don't run while paused or rewinding, so the buzzer stays quiet too.
+        if let Some(beeper) = beeper {
+            if chip8.platform() == Platform::XoChip {
+                beeper.set_pattern(chip8.audio_pattern(), chip8.audio_playback_rate());
+            }
+            beeper.set_active(chip8.is_sound_active() && !chip8.is_paused() && !rewinding);
+        }
+
+        // None when running as fast as possible.
+        let current_speed = if fast_forward { options.fast_forward } else { Some(speed) };
+        let label = match current_speed {
+            _ if chip8.is_paused() => String::from(" - paused"),
+            None => String::from(" - fast-forward"),
+            Some(1.0) => String::new(),
+            Some(speed) => format!(" - {}x", speed)
+        };
+        if label != speed_label {
+            // A title that can't be set only leaves the old one.
+            let _ = canvas.window_mut().set_title(&format!("{}{}", base_title, label));
+            speed_label = label;
+        }
+
+        // Frames of 1/60 second that passed on the clock since the last time round, however
+        // often the loop runs (it waits for the display's refresh in canvas.present).
+        let now = Instant::now();
+        scheduler.set_speed(current_speed.unwrap_or(1.0));
+        let mut frames = scheduler.advance(now - last_time);
+        last_time = now;
+        // Frame advance runs a single frame, then pauses again.
+        let advancing = std::mem::take(&mut advance_frame) && !rewinding;
+        if advancing {
+            chip8.resume();
+            frames = 1;
+        }
+        let uncapped = current_speed.is_none() && !rewinding && !advancing;
+
+        if rewinding {
+            for _ in 0..frames {
+                rewind_buffer.rewind(chip8);
+            }
+            draw_screen(chip8, canvas, &options.palette);
+            continue;
+        }
+
+        let mut frames_run = 0;
+        while if uncapped { now.elapsed() < UNCAPPED_RUN_TIME } else { frames_run < frames } {
+            frames_run += 1;
+            if let Some(movie_player) = player {
+                if !chip8.is_paused() {
+                    movie_player.play_frame(chip8)?;
+                }
+            }
+
+            scheduler.run_frame(chip8)?;
+            for hit in chip8.take_watch_hits() {
+                println!("Watch {}", format_watch_hit(&hit));
+            }
+            if chip8.is_halted() {
+                break 'gameloop;
+            }
+            if chip8.is_paused() {
+                if !stop_printed {
+                    print_debug_state(chip8);
+                    stop_printed = true;
+                }
+                break;
+            }
+            chip8.tick_timers();
+            rewind_buffer.push(chip8);
+
+            if let Some(movie_player) = player.take_if(|movie_player| movie_player.is_finished()) {
+                if movie_player.verify(chip8) {
+                    println!("Movie finished after {} frames, screen matches the recording", movie_player.frame());
+                } else {
+                    println!("Movie finished after {} frames, screen does NOT match the recording", movie_player.frame());
+                }
+            }
+        }
+
+        if advancing && !chip8.is_paused() {
+            chip8.pause();
+            stop_printed = true;
         }
-        chip8.tick_timers();
 
-        draw_screen(chip8, canvas);
+        draw_screen(chip8, canvas, &options.palette);
+        // Without vsync, or on a display faster than 60 Hz, wait instead of spinning.
+        if frames_run == 0 {
+            thread::sleep(scheduler.time_to_next_frame());
+        }
     }
+
+    Ok(())
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+// `desktop disasm ROM [--octo]`: prints a listing of the ROM, in Cowgod syntax unless --octo is given.
+fn disassemble_rom(rom_path: &str, syntax: Syntax) -> Result<(), String> {
+    let rom = fs::read(rom_path).map_err(|err| format!("Unable to open {}: {}", rom_path, err))?;
+    print!("{}", format_listing(&disassemble(&rom, 0x200), syntax));
+    Ok(())
+}
 
-    let mut games_folder_path: String = if args.len() > 1 { args[1].clone() }
-        else { DEFAULT_GAMES_FOLDER_PATH.to_string() };
-    if !games_folder_path.ends_with('/') {
-        games_folder_path.push('/');
+// --headless: runs options.frames frames (or the whole movie being played) without a window or sound,
+// then prints the screen and registers.
+fn run_headless(chip8: &mut Emulator, options: &Options, player: &mut Option<MoviePlayer>) -> Result<(), Chip8Error> {
+    let mut scheduler = match player {
+        Some(movie_player) => movie_player.movie().scheduler(),
+        None => Scheduler::with_timing(options.timing())
+    };
+    let print_watch_hits = |chip8: &mut Emulator| {
+        for hit in chip8.take_watch_hits() {
+            println!("Watch {}", format_watch_hit(&hit));
+        }
+    };
+    // Runs until the frames (or the movie) end, the program halts or the debugger stops it.
+    let (outcome, _) = run_frames(chip8, &mut scheduler, options.frames, &[], player.as_mut(), print_watch_hits);
+    if let RunOutcome::Crashed(err) = outcome {
+        return Err(err);
     }
 
-    let game_full_path: String = select_game(games_folder_path.as_str());
+    print!("{}", chip8.get_display());
+    print!("{}", format_registers(chip8));
+    Ok(())
+}
 
-    if !game_full_path.is_empty() {
-        let sdl_context: Sdl = sdl2::init().unwrap();
+fn run(mut options: Options) -> Result<(), String> {
+    let game_full_path = match &options.rom_path {
+        Some(path) if !Path::new(path).is_dir() => PathBuf::from(path),
+        Some(folder) => select_game(Path::new(folder)).map_err(|err| err.to_string())?,
+        None => {
+            let folder = match &options.rom_dir {
+                Some(folder) => PathBuf::from(folder),
+                None => env::current_dir().map_err(|err| err.to_string())?
+            };
+            select_game(&folder).map_err(|err| err.to_string())?
+        }
+    };
+    let game_full_path = game_full_path.to_string_lossy().into_owned();
+    let game_name = Path::new(&game_full_path).file_name().map_or(game_full_path.clone(), |name| name.to_string_lossy().into_owned());
+
+    let rom = fs::read(&game_full_path).map_err(|err| format!("Unable to open {}: {}", game_full_path, err))?;
+    if let Some(info) = RomDatabase::load()?.lookup(&rom) {
+        println!("Recognised {}", info.title);
+        info.apply(&mut options);
+    }
+    let rom_file = RomFile { path: game_full_path.clone(), hash: sha1_hex(&rom) };
+    if !options.given.keymap {
+        config::load_rom_keys(&rom_file.hash, &mut options.keymap)?;
+    }
+    let options = &options;
 
-        let game_name = game_full_path.split('/').last().unwrap();
-        let mut canvas: WindowCanvas = setup_canvas(&sdl_context, game_name);
-        let mut event_pump: EventPump = sdl_context.event_pump().unwrap();
+    let mut game = load_game(&game_full_path, &rom, options)?;
+    let mut player = match &options.movie_path {
+        Some(path) => Some(start_playback(&mut game, path).map_err(|err| format!("Unable to play movie {}: {}", path, err))?),
+        None => None
+    };
 
-        let mut game: Emulator = load_game(game_full_path.as_str());
+    if options.headless {
+        return run_headless(&mut game, options, &mut player).map_err(|err| format!("{} halted: {}", game_name, err));
+    }
 
-        main_loop(&mut game, &mut event_pump, &mut canvas);
+    let sdl_context: Sdl = sdl2::init()?;
+    let canvas: WindowCanvas = setup_canvas(&sdl_context, &game_name, options)?;
+    let event_pump: EventPump = sdl_context.event_pump()?;
+    let beeper = match Beeper::new(&sdl_context, options.beep) {
+        Ok(beeper) => Some(beeper),
+        Err(err) => {
+            eprintln!("Unable to open audio device, sound is disabled: {}", err);
+            None
+        }
+    };
+    let gamepads = match Gamepads::new(&sdl_context) {
+        Ok(gamepads) => Some(gamepads),
+        Err(err) => {
+            eprintln!("Unable to open game controllers, they are disabled: {}", err);
+            None
+        }
+    };
+    let mut frontend = Frontend { canvas, event_pump, beeper, gamepads };
+
+    main_loop(&mut game, &rom_file, options, &mut frontend, &mut player)
+        .map_err(|err| format!("{} halted: {}", game_name, err))
+}
+
+fn main() {
+    let defaults = match config::load() {
+        Ok(defaults) => defaults,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(EXIT_ERROR);
+        }
+    };
+
+    let command = match cli::parse_args(env::args().skip(1).collect(), defaults) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    let result = match command {
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        Command::Disassemble { rom_path, syntax } => disassemble_rom(&rom_path, syntax),
+        Command::Run(options) => run(*options)
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(EXIT_ERROR);
     }
 }
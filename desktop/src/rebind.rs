@@ -0,0 +1,138 @@
+// Rebinding screen: sets the host keys and controller buttons of each CHIP-8 key in turn, in keypad order.
+//
+// The window shows the keypad with the key being set highlighted, and its title tells
+// which host keys and controller buttons are bound to it. Keys and buttons pressed are added
+// to the highlighted key, except for the hotkeys; Enter moves on to the next key, Delete clears
+// the key and Esc cancels. Closing the window quits.
+
+use chip8_core::FONTSET;
+use sdl2::{controller::Button, event::Event, keyboard::Keycode, pixels::Color, rect::Rect, render::WindowCanvas, EventPump};
+
+use crate::gamepad::Gamepads;
+use crate::keymap::{Keymap, HOTKEYS, KEYPAD_ORDER};
+
+const FONT_HEIGHT: usize = 5;
+
+// How the rebinding screen was left.
+pub enum Rebinding {
+    Done,
+    Cancelled,
+    // The window was closed.
+    Quit,
+}
+
+fn draw_keypad(canvas: &mut WindowCanvas, keymap: &Keymap, current: usize, palette: &[Color; 4]) {
+    canvas.set_draw_color(palette[0]);
+    canvas.clear();
+
+    let (output_width, output_height) = canvas.output_size().unwrap_or((640, 320));
+    let cell = (output_width.min(output_height) / 4) as i32;
+    let left = (output_width as i32 - cell * 4) / 2;
+    let top = (output_height as i32 - cell * 4) / 2;
+    // Digits are 4x5 font pixels, drawn 8 font pixels tall in the middle of a cell.
+    let pixel = (cell / 8).max(1);
+
+    for (i, chip8_key) in KEYPAD_ORDER.into_iter().enumerate() {
+        let cell_left = left + (i % 4) as i32 * cell;
+        let cell_top = top + (i / 4) as i32 * cell;
+        if i == current {
+            canvas.set_draw_color(palette[3]);
+            // A failed rectangle only leaves part of the keypad out of this frame.
+            let _ = canvas.fill_rect(Rect::new(cell_left, cell_top, cell as u32, cell as u32));
+        }
+
+        // Keys without host keys or buttons are drawn dimmed.
+        let unbound = keymap.host_keys(chip8_key).is_empty() && keymap.buttons(chip8_key).is_empty();
+        canvas.set_draw_color(if unbound { palette[2] } else { palette[1] });
+        let digit_left = cell_left + (cell - 4 * pixel) / 2;
+        let digit_top = cell_top + (cell - FONT_HEIGHT as i32 * pixel) / 2;
+        for (row, bits) in FONTSET[chip8_key * FONT_HEIGHT..][..FONT_HEIGHT].iter().enumerate() {
+            for column in 0..4 {
+                if bits & (0x80 >> column) != 0 {
+                    let rect = Rect::new(digit_left + column * pixel, digit_top + row as i32 * pixel, pixel as u32, pixel as u32);
+                    let _ = canvas.fill_rect(rect);
+                }
+            }
+        }
+    }
+    canvas.present();
+}
+
+// Shows the bindings of the key being set in the window title, after `notice` if there is one.
+fn show_bindings(canvas: &mut WindowCanvas, keymap: &Keymap, chip8_key: usize, notice: Option<&str>) {
+    let names = keymap.host_keys(chip8_key).iter().map(|host_key| host_key.name())
+        .chain(keymap.buttons(chip8_key).iter().map(|button| format!("button {}", button.string())))
+        .collect::<Vec<String>>();
+    let names = if names.is_empty() { String::from("none") } else { names.join(", ") };
+    let mut title = format!("CHIP-8 key {:X}: {} - press keys or buttons to add, Enter: next, Delete: clear, Esc: cancel", chip8_key, names);
+    if let Some(notice) = notice {
+        title = format!("{} - {}", notice, title);
+    }
+    // A title that can't be set only leaves the old one.
+    let _ = canvas.window_mut().set_title(&title);
+}
+
+// Runs the rebinding screen. The keymap is only changed if the screen is done, not cancelled
+// or closed.
+pub fn rebind_keys(event_pump: &mut EventPump, canvas: &mut WindowCanvas, gamepads: &mut Option<Gamepads>,
+                   keymap: &mut Keymap, palette: &[Color; 4]) -> Rebinding {
+    let old_title = canvas.window().title().to_string();
+    let mut new_keymap = keymap.clone();
+    let mut current = 0;
+    // Host keys and buttons pressed for the current CHIP-8 key. The key keeps its host keys
+    // until one is pressed, and its buttons until one is pressed.
+    let mut pressed: Vec<Keycode> = Vec::new();
+    let mut pressed_buttons: Vec<Button> = Vec::new();
+    // Why the last key pressed wasn't bound, until the next key or button.
+    let mut notice: Option<String> = None;
+
+    let result = loop {
+        let Some(&chip8_key) = KEYPAD_ORDER.get(current) else {
+            *keymap = new_keymap;
+            break Rebinding::Done;
+        };
+        show_bindings(canvas, &new_keymap, chip8_key, notice.as_deref());
+        draw_keypad(canvas, &new_keymap, current, palette);
+
+        let event = event_pump.wait_event();
+        match event {
+            Event::Quit {..} => break Rebinding::Quit,
+            Event::KeyDown{keycode: Some(Keycode::Escape), ..} => break Rebinding::Cancelled,
+            Event::KeyDown{keycode: Some(host_key), repeat: false, ..} if HOTKEYS.contains(&host_key) => {
+                notice = Some(format!("{} is a hotkey and can't be bound", host_key.name()));
+            },
+            Event::KeyDown{keycode: Some(Keycode::Return | Keycode::KpEnter), repeat: false, ..} => {
+                notice = None;
+                current += 1;
+                pressed.clear();
+                pressed_buttons.clear();
+            },
+            Event::KeyDown{keycode: Some(Keycode::Delete), repeat: false, ..} => {
+                notice = None;
+                new_keymap.bind(chip8_key, Vec::new());
+                new_keymap.bind_buttons(chip8_key, Vec::new());
+                pressed.clear();
+                pressed_buttons.clear();
+            },
+            Event::KeyDown{keycode: Some(host_key), repeat: false, ..} if !pressed.contains(&host_key) => {
+                notice = None;
+                pressed.push(host_key);
+                new_keymap.bind(chip8_key, pressed.clone());
+            },
+            Event::ControllerButtonDown{button, ..} if !pressed_buttons.contains(&button) => {
+                notice = None;
+                pressed_buttons.push(button);
+                new_keymap.bind_buttons(chip8_key, pressed_buttons.clone());
+            },
+            Event::ControllerDeviceAdded {..} | Event::ControllerDeviceRemoved {..} => {
+                if let Some(gamepads) = gamepads {
+                    gamepads.handle_hotplug(&event);
+                }
+            },
+            _ => ()
+        }
+    };
+
+    let _ = canvas.window_mut().set_title(&old_title);
+    result
+}
@@ -0,0 +1,223 @@
+// ROM database: settings for known ROMs, looked up by the SHA-1 hash of the ROM.
+//
+// The database uses the layout of programs.json from the community CHIP-8 database
+// (https://github.com/chip-8/chip-8-database): an array of programs, each with a `title`
+// and a `roms` object keyed by SHA-1 hash. A small database is bundled; entries in
+// programs.json in the configuration folder are added to it and replace bundled ones,
+// so the community file can be dropped in as is.
+
+use std::{collections::HashMap, fs};
+
+use chip8_core::{Platform, Quirks, TIMER_HZ};
+use sdl2::{controller::Button, keyboard::Keycode, pixels::Color};
+
+use crate::cli::{parse_palette, Options};
+use crate::config;
+use crate::json::Json;
+use crate::keymap::NUM_CHIP8_KEYS;
+use crate::sha1::sha1_hex;
+
+const BUNDLED_DATABASE: &str = include_str!("../programs.json");
+
+// Host keys and controller buttons added for the CHIP-8 keys named by the `keys` of a ROM entry.
+const KEY_HINTS: [(&str, Keycode, Button); 6] = [
+    ("up", Keycode::Up, Button::DPadUp),
+    ("down", Keycode::Down, Button::DPadDown),
+    ("left", Keycode::Left, Button::DPadLeft),
+    ("right", Keycode::Right, Button::DPadRight),
+    ("a", Keycode::Space, Button::A),
+    ("b", Keycode::LShift, Button::B)
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub cpu_hz: Option<u32>,
+    pub palette: Option<[Color; 4]>,
+    // CHIP-8 keys with the host key and button that press them.
+    pub key_hints: Vec<(usize, Keycode, Button)>,
+}
+
+pub struct RomDatabase {
+    // Keyed by lowercase hex SHA-1.
+    roms: HashMap<String, RomInfo>,
+}
+
+// Platform and quirks for a platform id of the database, or None for platforms the
+// Emulator doesn't run.
+fn platform_for_id(id: &str) -> Option<(Platform, Quirks)> {
+    match id {
+        "originalChip8" | "hybridVIP" => Some((Platform::Chip8, Quirks::COSMAC_VIP)),
+        "modernChip8" => Some((Platform::Chip8, Quirks::default())),
+        "chip48" => Some((Platform::Chip8, Quirks::CHIP_48)),
+        "superchip1" | "superchip" => Some((Platform::SuperChip, Quirks::SUPER_CHIP)),
+        "xochip" => Some((Platform::XoChip, Quirks::XO_CHIP)),
+        _ => None
+    }
+}
+
+// Applies the database's quirk flags (e.g. `"shift": true`) on top of a platform's quirks.
+// Flags without an equivalent here are ignored.
+fn apply_quirk_flags(quirks: &mut Quirks, flags: &[(String, Json)]) {
+    for (name, value) in flags {
+        let Some(value) = value.as_bool() else {
+            continue;
+        };
+        match name.as_str() {
+            "shift" => quirks.shift_uses_vy = !value,
+            "memoryLeaveIUnchanged" => quirks.load_store_increments_i = !value,
+            "jump" => quirks.jump_uses_vx = value,
+            "logic" => quirks.vf_reset = value,
+            "wrap" => quirks.clipping = !value,
+            "vblank" => quirks.display_wait = value,
+            _ => ()
+        }
+    }
+}
+
+fn parse_rom(title: &str, rom: &Json) -> RomInfo {
+    // The first platform listed is the one the ROM was written for.
+    let platform = rom.get("platforms").and_then(Json::as_array).unwrap_or_default().iter()
+        .filter_map(Json::as_str)
+        .find_map(|id| Some((id, platform_for_id(id)?)));
+    let quirks = platform.map(|(id, (_, mut quirks))| {
+        if let Some(flags) = rom.get("quirkyPlatforms").and_then(|platforms| platforms.get(id)).and_then(Json::as_object) {
+            apply_quirk_flags(&mut quirks, flags);
+        }
+        quirks
+    });
+
+    // The tick rate is in instructions per frame of 1/60 second.
+    let cpu_hz = rom.get("tickrate").and_then(Json::as_f64)
+        .filter(|ticks| *ticks >= 1.0)
+        .map(|ticks| (ticks * TIMER_HZ as f64).min(u32::MAX as f64) as u32);
+
+    let palette = rom.get("colors").and_then(|colors| colors.get("pixels")).and_then(Json::as_array)
+        .and_then(|pixels| pixels.iter().map(Json::as_str).collect::<Option<Vec<&str>>>())
+        .and_then(|pixels| parse_palette(&pixels.join(",")));
+
+    let key_hints = KEY_HINTS.iter()
+        .filter_map(|(name, host_key, button)| {
+            let chip8_key = rom.get("keys")?.get(name)?.as_f64()? as usize;
+            Some((chip8_key, *host_key, *button)).filter(|_| chip8_key < NUM_CHIP8_KEYS)
+        })
+        .collect();
+
+    RomInfo {
+        title: title.to_string(),
+        platform: platform.map(|(_, (platform, _))| platform),
+        quirks,
+        cpu_hz,
+        palette,
+        key_hints
+    }
+}
+
+impl RomDatabase {
+    // The bundled database, extended by programs.json in the configuration folder if there is one.
+    pub fn load() -> Result<RomDatabase, String> {
+        let mut database = RomDatabase { roms: HashMap::new() };
+        database.add_programs(BUNDLED_DATABASE).map_err(|err| format!("Bundled ROM database: {}", err))?;
+
+        if let Some(path) = config::config_dir().map(|dir| dir.join("programs.json")).filter(|path| path.exists()) {
+            let text = fs::read_to_string(&path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+            database.add_programs(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+        Ok(database)
+    }
+
+    // Adds the ROMs of a programs.json file.
+    fn add_programs(&mut self, text: &str) -> Result<(), String> {
+        let programs = Json::parse(text)?;
+        for program in programs.as_array().ok_or("expected an array of programs")? {
+            let title = program.get("title").and_then(Json::as_str).unwrap_or("Untitled");
+            let roms = program.get("roms").and_then(Json::as_object)
+                .ok_or(format!("{} has no roms object", title))?;
+            for (hash, rom) in roms {
+                self.roms.insert(hash.to_ascii_lowercase(), parse_rom(title, rom));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.roms.get(&sha1_hex(rom))
+    }
+}
+
+impl RomInfo {
+    // Applies the ROM's settings to the options, except for settings given on the command line.
+    pub fn apply(&self, options: &mut Options) {
+        if self.platform.is_some() {
+            options.platform = self.platform;
+        }
+        if !options.given.quirks && self.quirks.is_some() {
+            // A display wait set in the configuration or on the command line still overrides
+            // the one of these quirks.
+            options.quirks = self.quirks;
+        }
+        if let Some(cpu_hz) = self.cpu_hz.filter(|_| !options.given.cpu_hz) {
+            options.cpu_hz = cpu_hz;
+        }
+        if let Some(palette) = self.palette.filter(|_| !options.given.palette) {
+            options.palette = palette;
+        }
+        for (chip8_key, host_key, button) in &self.key_hints {
+            options.keymap.add(*chip8_key, *host_key);
+            options.keymap.add_button(*chip8_key, *button);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled() -> RomDatabase {
+        let mut database = RomDatabase { roms: HashMap::new() };
+        database.add_programs(BUNDLED_DATABASE).unwrap();
+        database
+    }
+
+    #[test]
+    fn bundled_rom_is_found_by_its_hash() {
+        let rom = fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../games/roms/bug_game.ch8")).unwrap();
+        assert_eq!(sha1_hex(&rom), "68e8d0a7fe7485c3a62540d8757e9c1460157b0b");
+
+        let info = bundled().lookup(&rom).cloned().unwrap();
+        assert_eq!(info.title, "Bug Game");
+        assert_eq!(info.platform, Some(Platform::Chip8));
+        assert_eq!(info.quirks, Some(Quirks::default()));
+        assert_eq!(info.cpu_hz, Some(8 * TIMER_HZ));
+        assert_eq!(info.key_hints[0], (5, Keycode::Up, Button::DPadUp));
+        assert_eq!(bundled().lookup(b"not a rom"), None);
+    }
+
+    #[test]
+    fn quirk_flags_apply_to_the_first_known_platform() {
+        let mut database = RomDatabase { roms: HashMap::new() };
+        database.add_programs(r#"[{"title": "Test", "roms": {"ABCDEF": {
+            "platforms": ["megachip8", "superchip", "xochip"],
+            "quirkyPlatforms": {"superchip": {"shift": true, "wrap": true, "vblank": true, "unknown": true}},
+            "tickrate": 30
+        }}}]"#).unwrap();
+
+        let info = &database.roms["abcdef"];
+        assert_eq!(info.platform, Some(Platform::SuperChip));
+        assert_eq!(info.quirks, Some(Quirks { shift_uses_vy: false, clipping: false, display_wait: true, ..Quirks::SUPER_CHIP }));
+        assert_eq!(info.cpu_hz, Some(30 * TIMER_HZ));
+    }
+
+    #[test]
+    fn rom_quirks_keep_the_display_wait_setting() {
+        let mut database = RomDatabase { roms: HashMap::new() };
+        database.add_programs(r#"[{"title": "Test", "roms": {"abcdef": {"platforms": ["originalChip8"]}}}]"#).unwrap();
+
+        let mut options = Options { display_wait: Some(false), ..Options::default() };
+        database.roms["abcdef"].apply(&mut options);
+        assert_eq!(options.quirks, Some(Quirks::COSMAC_VIP));
+        assert_eq!(options.display_wait, Some(false));
+    }
+}
@@ -0,0 +1,275 @@
+// Headless runner: executes a ROM without a window or interactive input, for CI and scripts.
+// Prints the final screen, registers and (optionally) memory once the run stops.
+
+use chip8_core::*;
+
+use std::{env, fmt::Write as _, fs, process};
+
+const DEFAULT_FRAMES: usize = 600;  // 10 seconds at 60 fps
+const DEFAULT_TICKS_PER_FRAME: usize = 8;
+
+// Exit codes.
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_PC_NOT_REACHED: i32 = 3;
+const EXIT_MOVIE_MISMATCH: i32 = 4;
+
+const USAGE: &str = "\
+Usage: headless ROM [OPTIONS]
+
+Runs ROM without a display and prints the final screen and registers.
+
+Options:
+  --frames N              Frames to run, at 60 frames per second (default 600)
+  --ticks-per-frame N     Instructions executed per frame (default 8)
+  --vip-timing            Time instructions like the COSMAC VIP, instead of --ticks-per-frame
+  --platform NAME         chip8, schip or xochip (default: from the ROM extension)
+  --quirks PRESET         vip, chip48, schip or xochip (default: the platform's)
+  --display-wait on|off   Whether DXYN waits for the vertical blank (default: the quirks')
+  --key-wait press|release
+                          Whether FX0A takes a held key or waits for its release (default release)
+  --seed N                Seed for the random numbers of CXKK (default 0)
+  --until-pc ADDR         Stop as soon as PC reaches the hex address ADDR
+  --key FRAME:KEY[:HOLD]  Hold hex key KEY from FRAME for HOLD frames (default 1)
+  --keys FILE             Read --key entries from FILE, one per line; # starts a comment
+  --play MOVIE            Play back the keys of a movie with its recorded timing, for as many
+                          frames as it lasts
+  --record MOVIE          Record the run, including the scripted keys, to a movie
+  --memory                Also dump RAM
+  --output FILE           Write the report to FILE instead of stdout
+  --help                  Print this message
+
+Exit codes: 0 on success, 1 if the ROM can't be loaded or crashes,
+2 on bad arguments, 3 if --until-pc was given and PC never reached it,
+4 if the screen at the end of --play differs from the recording.";
+
+struct Options {
+    rom_path: String,
+    frames: usize,
+    ticks_per_frame: usize,
+    vip_timing: bool,
+    rom: RomSettings,
+    until_pc: Option<usize>,
+    keys: Vec<KeyPress>,
+    play: Option<String>,
+    record: Option<String>,
+    memory: bool,
+    output: Option<String>,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(EXIT_USAGE);
+}
+
+// Parses `FRAME:KEY[:HOLD]`, e.g. `120:5` or `120:A:30`.
+fn parse_key_press(text: &str) -> Option<KeyPress> {
+    let mut parts = text.trim().split(':');
+    let frame = parts.next()?.parse().ok()?;
+    let key = usize::from_str_radix(parts.next()?, 16).ok().filter(|key| *key < 16)?;
+    let hold = match parts.next() {
+        Some(hold) => hold.parse().ok().filter(|hold| *hold > 0)?,
+        None => 1
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(KeyPress { frame, key, hold })
+}
+
+fn read_key_file(path: &str) -> Vec<KeyPress> {
+    let contents = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("Unable to read {}: {}", path, err);
+        process::exit(EXIT_ERROR);
+    });
+
+    contents.lines()
+        .enumerate()
+        .map(|(number, line)| (number, line.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(number, line)| parse_key_press(line)
+            .unwrap_or_else(|| usage_error(&format!("{}:{}: expected FRAME:KEY[:HOLD], got `{}`", path, number + 1, line))))
+        .collect()
+}
+
+fn parse_args(args: Vec<String>) -> Options {
+    let mut options = Options {
+        rom_path: String::new(),
+        frames: DEFAULT_FRAMES,
+        ticks_per_frame: DEFAULT_TICKS_PER_FRAME,
+        vip_timing: false,
+        rom: RomSettings { seed: Some(0), ..RomSettings::default() },
+        until_pc: None,
+        keys: Vec::new(),
+        play: None,
+        record: None,
+        memory: false,
+        output: None
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage_error(&format!("{} expects a value", arg)));
+        match arg.as_str() {
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            },
+            "--frames" => {
+                options.frames = value().parse().unwrap_or_else(|_| usage_error("--frames expects a number"));
+            },
+            "--ticks-per-frame" => {
+                options.ticks_per_frame = value().parse().unwrap_or_else(|_| usage_error("--ticks-per-frame expects a number"));
+            },
+            "--vip-timing" => options.vip_timing = true,
+            "--platform" => {
+                let name = value();
+                options.rom.platform = Some(Platform::from_name(&name).unwrap_or_else(||
+                    usage_error(&format!("Unknown platform `{}`, expected one of {}", name, Platform::NAMES.join(", ")))));
+            },
+            "--quirks" => {
+                let name = value();
+                options.rom.quirks = Some(Quirks::from_preset_name(&name).unwrap_or_else(||
+                    usage_error(&format!("Unknown quirks preset `{}`, expected one of {}", name, Quirks::PRESET_NAMES.join(", ")))));
+            },
+            "--display-wait" => {
+                options.rom.display_wait = Some(match value().as_str() {
+                    "on" => true,
+                    "off" => false,
+                    _ => usage_error("--display-wait expects on or off")
+                });
+            },
+            "--key-wait" => {
+                options.rom.key_wait_on_press = match value().as_str() {
+                    "press" => true,
+                    "release" => false,
+                    _ => usage_error("--key-wait expects press or release")
+                };
+            },
+            "--seed" => {
+                options.rom.seed = Some(value().parse().unwrap_or_else(|_| usage_error("--seed expects a number")));
+            },
+            "--until-pc" => {
+                options.until_pc = Some(parse_address(&value()).unwrap_or_else(|| usage_error("--until-pc expects a hex address, e.g. 0x2A4")));
+            },
+            "--key" => {
+                let press = parse_key_press(&value()).unwrap_or_else(|| usage_error("--key expects FRAME:KEY[:HOLD], e.g. 120:5:30"));
+                options.keys.push(press);
+            },
+            "--keys" => {
+                let path = value();
+                options.keys.extend(read_key_file(&path));
+            },
+            "--play" => options.play = Some(value()),
+            "--record" => options.record = Some(value()),
+            "--memory" => options.memory = true,
+            "--output" => options.output = Some(value()),
+            _ if arg.starts_with('-') => usage_error(&format!("Unknown option {}", arg)),
+            _ if options.rom_path.is_empty() => options.rom_path = arg,
+            _ => usage_error(&format!("Unexpected argument {}", arg))
+        }
+    }
+
+    if options.rom_path.is_empty() {
+        usage_error("No ROM given");
+    }
+    if options.play.is_some() && !options.keys.is_empty() {
+        usage_error("--play takes its keys from the movie, it can't be combined with --key or --keys");
+    }
+    options
+}
+
+fn load(options: &Options) -> Result<Emulator, String> {
+    let rom = fs::read(&options.rom_path).map_err(|err| format!("Unable to open {}: {}", options.rom_path, err))?;
+    load_rom(&options.rom_path, &rom, &options.rom).map_err(|err| format!("Unable to load {}: {}", options.rom_path, err))
+}
+
+fn load_movie(path: &str, chip8: &mut Emulator) -> Result<MoviePlayer, String> {
+    let data = fs::read(path).map_err(|err| format!("Unable to open {}: {}", path, err))?;
+    let movie = Movie::from_bytes(&data).map_err(|err| format!("Unable to load {}: {}", path, err))?;
+    MoviePlayer::start(movie, chip8).map_err(|err| format!("Unable to play {}: {}", path, err))
+}
+
+fn format_report(chip8: &Emulator, outcome: &RunOutcome, frames: usize, memory: bool) -> String {
+    let mut report = String::new();
+
+    match outcome {
+        RunOutcome::FramesElapsed => writeln!(report, "Ran {} frames", frames),
+        RunOutcome::Stopped(StopReason::Breakpoint { addr }) => writeln!(report, "Reached PC {:#05X} in frame {}", addr, frames),
+        RunOutcome::Stopped(reason) => writeln!(report, "Stopped in frame {}: {:?}", frames, reason),
+        RunOutcome::Halted => writeln!(report, "Halted in frame {}", frames),
+        RunOutcome::Crashed(err) => writeln!(report, "Crashed in frame {}: {}", frames, err)
+    }.unwrap();
+
+    report += &format_registers(chip8);
+
+    let display = chip8.get_display();
+    writeln!(report, "\nScreen {}x{}", display.width, display.height).unwrap();
+    write!(report, "{}", display).unwrap();
+
+    if memory {
+        writeln!(report, "\nMemory").unwrap();
+        for (row, bytes) in chip8.memory().chunks(16).enumerate() {
+            let line: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            writeln!(report, "{:#06X}  {}", row * 16, line.join(" ")).unwrap();
+        }
+    }
+
+    report
+}
+
+fn main() {
+    let options = parse_args(env::args().skip(1).collect());
+
+    let mut chip8 = load(&options).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(EXIT_ERROR);
+    });
+
+    let mut player = options.play.as_deref().map(|path| load_movie(path, &mut chip8).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(EXIT_ERROR);
+    }));
+    // A movie is played back with the timing it was recorded with.
+    let mut scheduler = match &player {
+        Some(player) => player.movie().scheduler(),
+        None if options.vip_timing => Scheduler::with_timing(Timing::CosmacVip),
+        None => Scheduler::new((options.ticks_per_frame as u32).saturating_mul(TIMER_HZ))
+    };
+    if options.record.is_some() {
+        chip8.start_recording(&mut scheduler);
+    }
+
+    if let Some(addr) = options.until_pc {
+        chip8.add_breakpoint(addr);
+    }
+    let (outcome, frames) = run_frames(&mut chip8, &mut scheduler, options.frames, &options.keys, player.as_mut(), |_| ());
+    let mut report = format_report(&chip8, &outcome, frames, options.memory);
+
+    let movie_matches = player.as_ref().is_none_or(|player| player.verify(&chip8));
+    if !movie_matches {
+        report.insert_str(0, "Screen does not match the end of the movie\n");
+    }
+
+    if let (Some(path), Some(movie)) = (&options.record, chip8.stop_recording()) {
+        if let Err(err) = fs::write(path, movie.to_bytes()) {
+            eprintln!("Unable to write {}: {}", path, err);
+            process::exit(EXIT_ERROR);
+        }
+    }
+
+    match &options.output {
+        Some(path) => if let Err(err) = fs::write(path, &report) {
+            eprintln!("Unable to write {}: {}", path, err);
+            process::exit(EXIT_ERROR);
+        },
+        None => print!("{}", report)
+    }
+
+    match outcome {
+        RunOutcome::Crashed(_) => process::exit(EXIT_ERROR),
+        RunOutcome::FramesElapsed | RunOutcome::Halted if options.until_pc.is_some() => process::exit(EXIT_PC_NOT_REACHED),
+        _ if !movie_matches => process::exit(EXIT_MOVIE_MISMATCH),
+        _ => ()
+    }
+}